@@ -0,0 +1,755 @@
+use std::error::Error;
+
+use chrono::{DateTime, Utc};
+use serde_json::{json, Value};
+
+use crate::commands::{self, Candidates, ExistingQuestion, ExportSource, ImportOptions, ImportTarget, NewCard, NewDashboard, NewQuestion, PublicUuid};
+use crate::model::*;
+use crate::snapshot::*;
+
+pub struct ApiOptions {
+    /// The url of metabase (e.g. `https://metabase.example.com`)
+    pub url: String,
+
+    /// An api key, sent as `x-api-key`
+    pub api_key: Option<String>,
+
+    /// A session id, sent as `X-Metabase-Session`
+    pub session: Option<String>,
+
+    /// The credentials used to create a session, when there's neither an api key nor a session
+    pub username: Option<String>,
+    pub password: Option<String>,
+}
+
+/// A client for metabase's http api, used where the application database isn't reachable.
+pub struct Client {
+    url: String,
+    auth: (&'static str, String),
+}
+
+impl Client {
+    pub fn connect(options: &ApiOptions) -> Result<Client, Box<dyn Error>> {
+        let url = options.url.trim_end_matches('/').to_string();
+        let auth = match (&options.api_key, &options.session, &options.username, &options.password) {
+            (Some(api_key), _, _, _) => ("x-api-key", api_key.clone()),
+            (None, Some(session), _, _) => ("X-Metabase-Session", session.clone()),
+            (None, None, Some(username), Some(password)) => {
+                let session = ureq::post(&format!("{}/api/session", url))
+                    .send_json(json!({ "username": username, "password": password }))
+                    .map_err(|err| request_error("/api/session", err))?
+                    .into_json::<Value>()?;
+                let id = session["id"].as_str().ok_or(crate::error!("expected the session to have an `id`"))?;
+                ("X-Metabase-Session", id.to_string())
+            }
+            _ => return Err(crate::error!("the api needs an --api-key, a --session, or METABASE_USERNAME and METABASE_PASSWORD")),
+        };
+        Ok(Client { url, auth })
+    }
+
+    fn get(&self, path: &str) -> Result<Value, Box<dyn Error>> {
+        let response = ureq::get(&format!("{}{}", self.url, path))
+            .set(self.auth.0, &self.auth.1)
+            .call()
+            .map_err(|err| request_error(path, err))?;
+        Ok(response.into_json()?)
+    }
+
+    fn post(&self, path: &str, body: Value) -> Result<Value, Box<dyn Error>> {
+        let response = ureq::post(&format!("{}{}", self.url, path))
+            .set(self.auth.0, &self.auth.1)
+            .send_json(body)
+            .map_err(|err| request_error(path, err))?;
+        Ok(response.into_json()?)
+    }
+
+    fn put(&self, path: &str, body: Value) -> Result<Value, Box<dyn Error>> {
+        let response = ureq::put(&format!("{}{}", self.url, path))
+            .set(self.auth.0, &self.auth.1)
+            .send_json(body)
+            .map_err(|err| request_error(path, err))?;
+        Ok(response.into_json()?)
+    }
+
+    /// Loads the collections, databases, tables and fields, like `Cache::load`.
+    pub fn load_cache(&self) -> Result<Cache, Box<dyn Error>> {
+        let collections = list(self.get("/api/collection")?)
+            .iter()
+            // The root collection is listed with the id "root"
+            .filter(|x| x["id"].is_i64())
+            .map(collection)
+            .collect::<Result<Vec<_>, _>>()?;
+        let databases = list(self.get("/api/database")?)
+            .iter()
+            .map(database)
+            .collect::<Result<Vec<_>, _>>()?;
+        // The metadata of a database has its tables, with their fields
+        let mut tables = Vec::new();
+        let mut fields = Vec::new();
+        for database in &databases {
+            let metadata = self.get(&format!("/api/database/{}/metadata", database.id))?;
+            for value in list(metadata["tables"].clone()) {
+                for field_value in list(value["fields"].clone()) {
+                    fields.push(field(&field_value)?);
+                }
+                tables.push(table(&value)?);
+            }
+        }
+        Ok(Cache::new(collections, databases, tables, fields))
+    }
+}
+
+/// Imports a question or dashboard through metabase's api.
+///
+/// Unlike importing into the database, this isn't done in a transaction, so a failed dashboard import
+/// can leave the dashboard (and any collections it created) behind.
+pub fn import(api: &ApiOptions, filename: &str, options: &ImportOptions) -> Result<(), Box<dyn Error>> {
+    if options.public_uuid == PublicUuid::Preserve {
+        return Err(crate::error!("the api can't preserve public links (use --public-uuid generate)"));
+    }
+    let client = Client::connect(api)?;
+    let cache = client.load_cache()?;
+    let snapshot_bytes = std::fs::read(filename)?;
+    let snapshot: Snapshot = serde_json::from_slice(&snapshot_bytes)?;
+    match snapshot {
+        Snapshot::Question(question) => {
+            let id = commands::restore_question(&client, &cache, question, options)?;
+            eprintln!("imported question {}", id);
+        }
+        Snapshot::Dashboard(dashboard) => {
+            let id = commands::restore_dashboard(&client, &cache, dashboard, options)?;
+            eprintln!("imported dashboard {}", id);
+        }
+        _ => return Err(crate::error!("only questions and dashboards can be imported through the api")),
+    }
+    Ok(())
+}
+
+/// Exports a question or dashboard through metabase's api.
+pub fn export(api: &ApiOptions, source: &str, id: Option<&str>) -> Result<(), Box<dyn Error>> {
+    let id = id.ok_or(crate::error!("the {} to export must be given", source))?;
+    let client = Client::connect(api)?;
+    let cache = client.load_cache()?;
+    let snapshot = match source {
+        "question" => {
+            let id = commands::find_question_id(&client, &cache, id)?;
+            Snapshot::Question(commands::snapshot_question(&client, &cache, id)?)
+        }
+        "dashboard" => {
+            let id = commands::find_dashboard_id(&client, &cache, id)?;
+            Snapshot::Dashboard(commands::snapshot_dashboard(&client, &cache, id)?)
+        }
+        _ => return Err(crate::error!("only questions and dashboards can be exported through the api")),
+    };
+    println!("{}", serde_json::to_string(&snapshot)?);
+    Ok(())
+}
+
+impl ExportSource for Client {
+    fn load_question(&self, id: i32) -> Result<Question, Box<dyn Error>> {
+        card(&self.get(&format!("/api/card/{}", id))?)
+    }
+
+    /// Labels aren't part of metabase's api, so questions are exported without them.
+    fn question_labels(&self, _question_id: i32) -> Result<Vec<Label>, Box<dyn Error>> {
+        Ok(Vec::new())
+    }
+
+    fn load_dashboard(&self, id: i32) -> Result<Dashboard, Box<dyn Error>> {
+        dashboard(&self.get(&format!("/api/dashboard/{}", id))?)
+    }
+
+    fn dashboard_cards(&self, dashboard_id: i32) -> Result<Vec<DashboardCard>, Box<dyn Error>> {
+        let dashboard = self.get(&format!("/api/dashboard/{}", dashboard_id))?;
+        // Newer versions of metabase renamed `ordered_cards` to `dashcards`
+        let cards = if dashboard["dashcards"].is_array() { &dashboard["dashcards"] } else { &dashboard["ordered_cards"] };
+        list(cards.clone()).iter().map(dashboard_card).collect()
+    }
+
+    fn questions_named(&self, name: &str) -> Result<Candidates, Box<dyn Error>> {
+        named(list(self.get("/api/card")?), name)
+    }
+
+    fn dashboards_named(&self, name: &str) -> Result<Candidates, Box<dyn Error>> {
+        named(list(self.get("/api/dashboard")?), name)
+    }
+}
+
+impl ImportTarget for Client {
+    fn import_collection(&self, cache: &Cache, dep: &Option<CollectionDependency>, options: &ImportOptions) -> Result<Option<i32>, Box<dyn Error>> {
+        import_collection(self, cache, dep, options)
+    }
+
+    fn question_exists(&self, name: &str, collection_id: Option<i32>) -> Result<bool, Box<dyn Error>> {
+        Ok(self.find_question(name, collection_id)?.is_some())
+    }
+
+    fn dashboard_exists(&self, name: &str, collection_id: Option<i32>) -> Result<bool, Box<dyn Error>> {
+        Ok(list(self.get("/api/dashboard")?)
+            .iter()
+            .any(|x| x["name"] == name && x["collection_id"] == json!(collection_id)))
+    }
+
+    fn question(&self, id: i32) -> Result<ExistingQuestion, Box<dyn Error>> {
+        let card = self.get(&format!("/api/card/{}", id))?;
+        Ok(ExistingQuestion { id, table_id: optional_integer(&card, "table_id") })
+    }
+
+    fn find_question(&self, name: &str, collection_id: Option<i32>) -> Result<Option<ExistingQuestion>, Box<dyn Error>> {
+        list(self.get("/api/card")?)
+            .iter()
+            .find(|x| x["name"] == name && x["collection_id"] == json!(collection_id))
+            .map(|x| Ok(ExistingQuestion { id: id(x)?, table_id: optional_integer(x, "table_id") }))
+            .transpose()
+    }
+
+    fn create_question(&self, question: NewQuestion, options: &ImportOptions) -> Result<i32, Box<dyn Error>> {
+        if !question.labels.is_empty() {
+            eprintln!("warning: labels aren't imported through the api (question: {})", question.name);
+        }
+        let card = self.post("/api/card", json!({
+            "name": question.name,
+            "description": question.description,
+            "display": question.display,
+            "dataset_query": json_text(&question.query)?,
+            "visualization_settings": json_text(&question.settings)?,
+            "collection_id": question.collection_id,
+            "collection_position": question.collection_position,
+            "result_metadata": question.result_metadata.as_deref().map(json_text).transpose()?,
+        }))?;
+        let question_id = id(&card)?;
+        if question.cache_ttl.is_some() || question.enable_embedding {
+            self.put(&format!("/api/card/{}", question_id), json!({
+                "cache_ttl": question.cache_ttl,
+                "enable_embedding": question.enable_embedding,
+                "embedding_params": question.embedding_params.as_deref().map(json_text).transpose()?,
+            }))?;
+        }
+        if question.public_uuid.is_some() && options.public_uuid == PublicUuid::Generate {
+            self.post(&format!("/api/card/{}/public_link", question_id), json!({}))?;
+        }
+        Ok(question_id)
+    }
+
+    fn create_dashboard(&self, dashboard: NewDashboard, cards: Vec<NewCard>, options: &ImportOptions) -> Result<i32, Box<dyn Error>> {
+        let created = self.post("/api/dashboard", json!({
+            "name": dashboard.name,
+            "description": dashboard.description,
+            "parameters": json_text(&dashboard.parameters)?,
+            "collection_id": dashboard.collection_id,
+            "collection_position": dashboard.collection_position,
+        }))?;
+        let dashboard_id = id(&created)?;
+        let mut layout = Vec::new();
+        for card in cards {
+            // Text and heading cards are added without a `cardId`
+            let dashcard = self.post(&format!("/api/dashboard/{}/cards", dashboard_id), json!({ "cardId": card.question_id }))?;
+            layout.push(json!({
+                "id": id(&dashcard)?,
+                "card_id": card.question_id,
+                "sizeX": card.size_x,
+                "sizeY": card.size_y,
+                "row": card.row,
+                "col": card.col,
+                "parameter_mappings": json_text(&card.parameters)?,
+                "visualization_settings": json_text(&card.settings)?,
+                "series": [],
+            }));
+        }
+        // Cards are added without their layout or mappings, which are then set for all the cards at once
+        self.put(&format!("/api/dashboard/{}/cards", dashboard_id), json!({ "cards": layout }))?;
+        if dashboard.enable_embedding {
+            self.put(&format!("/api/dashboard/{}", dashboard_id), json!({
+                "enable_embedding": dashboard.enable_embedding,
+                "embedding_params": dashboard.embedding_params.as_deref().map(json_text).transpose()?,
+            }))?;
+        }
+        if dashboard.public_uuid.is_some() && options.public_uuid == PublicUuid::Generate {
+            self.post(&format!("/api/dashboard/{}/public_link", dashboard_id), json!({}))?;
+        }
+        Ok(dashboard_id)
+    }
+}
+
+/// Finds (or creates) the collection to import into, like `commands::resolve_collection_path`.
+fn import_collection(client: &Client, cache: &Cache, dep: &Option<CollectionDependency>, options: &ImportOptions) -> Result<Option<i32>, Box<dyn Error>> {
+    let path = match (&options.into_collection, dep) {
+        (Some(path), _) => path.clone(),
+        (None, Some(dep)) if options.create_collections => dep.path(),
+        (None, Some(dep)) => return Ok(Some(cache.find_collection(&dep.path())?.id)),
+        (None, None) => return Ok(None),
+    };
+    let mut collection_id = None;
+    let mut location = Some("/".to_string());
+    let mut color = commands::DEFAULT_COLLECTION_COLOR.to_string();
+    for name in &path {
+        // Once a collection has been created, its children can't exist (and aren't in the cache)
+        let mut existing = match &location {
+            Some(location) => cache.collections()
+                .filter(|x| &x.name == name && &x.location == location && !x.archived)
+                .collect::<Vec<_>>(),
+            None => Vec::new(),
+        };
+        if existing.len() > 1 {
+            let ids = existing.iter().map(|x| x.id.to_string()).collect::<Vec<_>>();
+            return Err(crate::error!("collection is ambiguous (collection: {}, candidates: {})", path.join("/"), ids.join(", ")));
+        }
+        match existing.pop() {
+            Some(collection) => {
+                location = Some(format!("{}{}/", collection.location, collection.id));
+                color = collection.color.clone();
+                collection_id = Some(collection.id);
+            }
+            None if options.create_collections => {
+                let created = client.post("/api/collection", json!({ "name": name, "color": color, "parent_id": collection_id }))?;
+                location = None;
+                collection_id = Some(id(&created)?);
+            }
+            None => return Err(crate::error!("collection doesn't exist (collection: {})", path.join("/"))),
+        }
+    }
+    Ok(collection_id)
+}
+
+/// The (non-archived) items of a list with a name.
+fn named(items: Vec<Value>, name: &str) -> Result<Candidates, Box<dyn Error>> {
+    items.iter()
+        .filter(|x| x["name"] == name && !boolean(x, "archived"))
+        .map(|x| Ok((id(x)?, optional_integer(x, "collection_id"))))
+        .collect()
+}
+
+fn request_error(path: &str, err: ureq::Error) -> Box<dyn Error> {
+    match err {
+        ureq::Error::Status(status, response) => {
+            let body = response.into_string().unwrap_or_default();
+            crate::error!("api request failed (path: {}, status: {}): {}", path, status, body)
+        }
+        err => crate::error!("api request failed (path: {}): {}", path, err),
+    }
+}
+
+/// Returns the items of a list response (newer versions of metabase wrap some lists as `{"data": [...]}`).
+fn list(value: Value) -> Vec<Value> {
+    match value {
+        Value::Array(items) => items,
+        Value::Object(mut object) => match object.remove("data") {
+            Some(Value::Array(items)) => items,
+            _ => Vec::new(),
+        },
+        _ => Vec::new(),
+    }
+}
+
+/// Parses the json held in a text column (the api takes it as json rather than as a string).
+fn json_text(text: &str) -> Result<Value, Box<dyn Error>> {
+    Ok(serde_json::from_str(text)?)
+}
+
+fn id(value: &Value) -> Result<i32, Box<dyn Error>> {
+    integer(value, "id")
+}
+
+fn integer(value: &Value, key: &str) -> Result<i32, Box<dyn Error>> {
+    value[key].as_i64().map(|x| x as i32).ok_or_else(|| crate::error!("expected `{}` to be an integer", key).into())
+}
+
+fn optional_integer(value: &Value, key: &str) -> Option<i32> {
+    value[key].as_i64().map(|x| x as i32)
+}
+
+fn string(value: &Value, key: &str) -> String {
+    value[key].as_str().unwrap_or_default().to_string()
+}
+
+/// Returns a string, or the json of an object (like the text columns that hold json in the database).
+fn optional_string(value: &Value, key: &str) -> Option<String> {
+    match &value[key] {
+        Value::Null => None,
+        Value::String(x) => Some(x.clone()),
+        other => Some(other.to_string()),
+    }
+}
+
+fn boolean(value: &Value, key: &str) -> bool {
+    value[key].as_bool().unwrap_or(false)
+}
+
+fn timestamp(value: &Value, key: &str) -> Option<DateTime<Utc>> {
+    value[key].as_str().and_then(|x| DateTime::parse_from_rfc3339(x).ok()).map(|x| x.with_timezone(&Utc))
+}
+
+fn collection(value: &Value) -> Result<Collection, Box<dyn Error>> {
+    Ok(Collection {
+        id: id(value)?,
+        name: string(value, "name"),
+        description: optional_string(value, "description"),
+        color: string(value, "color"),
+        archived: boolean(value, "archived"),
+        location: optional_string(value, "location").unwrap_or_else(|| "/".to_string()),
+        personal_owner_id: optional_integer(value, "personal_owner_id"),
+        slug: string(value, "slug"),
+    })
+}
+
+fn card(value: &Value) -> Result<Question, Box<dyn Error>> {
+    Ok(Question {
+        id: id(value)?,
+        created_at: timestamp(value, "created_at").unwrap_or_else(Utc::now),
+        updated_at: timestamp(value, "updated_at").unwrap_or_else(Utc::now),
+        name: string(value, "name"),
+        description: optional_string(value, "description"),
+        display: string(value, "display"),
+        dataset_query: optional_string(value, "dataset_query").unwrap_or_else(|| "{}".to_string()),
+        visualization_settings: optional_string(value, "visualization_settings").unwrap_or_else(|| "{}".to_string()),
+        creator_id: integer(value, "creator_id")?,
+        database_id: optional_integer(value, "database_id"),
+        table_id: optional_integer(value, "table_id"),
+        query_type: optional_string(value, "query_type"),
+        archived: boolean(value, "archived"),
+        collection_id: optional_integer(value, "collection_id"),
+        public_uuid: optional_string(value, "public_uuid"),
+        made_public_by_id: optional_integer(value, "made_public_by_id"),
+        enable_embedding: boolean(value, "enable_embedding"),
+        embedding_params: optional_string(value, "embedding_params"),
+        cache_ttl: optional_integer(value, "cache_ttl"),
+        result_metadata: optional_string(value, "result_metadata"),
+        read_permissions: optional_string(value, "read_permissions"),
+        collection_position: optional_integer(value, "collection_position").map(|x| x as i16),
+    })
+}
+
+fn dashboard(value: &Value) -> Result<Dashboard, Box<dyn Error>> {
+    Ok(Dashboard {
+        id: id(value)?,
+        created_at: timestamp(value, "created_at").unwrap_or_else(Utc::now),
+        updated_at: timestamp(value, "updated_at").unwrap_or_else(Utc::now),
+        name: string(value, "name"),
+        description: optional_string(value, "description"),
+        creator_id: integer(value, "creator_id")?,
+        parameters: optional_string(value, "parameters").unwrap_or_else(|| "[]".to_string()),
+        points_of_interest: optional_string(value, "points_of_interest"),
+        caveats: optional_string(value, "caveats"),
+        show_in_getting_started: boolean(value, "show_in_getting_started"),
+        public_uuid: optional_string(value, "public_uuid"),
+        made_public_by_id: optional_integer(value, "made_public_by_id"),
+        enable_embedding: boolean(value, "enable_embedding"),
+        embedding_params: optional_string(value, "embedding_params"),
+        archived: boolean(value, "archived"),
+        position: optional_integer(value, "position"),
+        collection_id: optional_integer(value, "collection_id"),
+        collection_position: optional_integer(value, "collection_position").map(|x| x as i16),
+    })
+}
+
+fn dashboard_card(value: &Value) -> Result<DashboardCard, Box<dyn Error>> {
+    // Newer versions of metabase renamed `sizeX`/`sizeY` to `size_x`/`size_y`
+    let size = |key: &str, old_key: &str| optional_integer(value, key).or_else(|| optional_integer(value, old_key)).unwrap_or(0);
+    Ok(DashboardCard {
+        id: id(value)?,
+        created_at: timestamp(value, "created_at").unwrap_or_else(Utc::now),
+        updated_at: timestamp(value, "updated_at").unwrap_or_else(Utc::now),
+        size_x: size("size_x", "sizeX"),
+        size_y: size("size_y", "sizeY"),
+        row: optional_integer(value, "row").unwrap_or(0),
+        col: optional_integer(value, "col").unwrap_or(0),
+        card_id: optional_integer(value, "card_id"),
+        dashboard_id: integer(value, "dashboard_id")?,
+        parameter_mappings: optional_string(value, "parameter_mappings").unwrap_or_else(|| "[]".to_string()),
+        visualization_settings: optional_string(value, "visualization_settings").unwrap_or_else(|| "{}".to_string()),
+    })
+}
+
+fn database(value: &Value) -> Result<Database, Box<dyn Error>> {
+    Ok(Database {
+        id: id(value)?,
+        created_at: timestamp(value, "created_at").unwrap_or_else(Utc::now),
+        updated_at: timestamp(value, "updated_at").unwrap_or_else(Utc::now),
+        name: string(value, "name"),
+        description: optional_string(value, "description"),
+        details: optional_string(value, "details"),
+        engine: string(value, "engine"),
+        is_sample: boolean(value, "is_sample"),
+        is_full_sync: boolean(value, "is_full_sync"),
+        points_of_interest: optional_string(value, "points_of_interest"),
+        caveats: optional_string(value, "caveats"),
+        metadata_sync_schedule: string(value, "metadata_sync_schedule"),
+        cache_field_values_schedule: string(value, "cache_field_values_schedule"),
+        timezone: optional_string(value, "timezone"),
+        is_on_demand: boolean(value, "is_on_demand"),
+        options: optional_string(value, "options"),
+    })
+}
+
+fn table(value: &Value) -> Result<DbTable, Box<dyn Error>> {
+    Ok(DbTable {
+        id: id(value)?,
+        created_at: timestamp(value, "created_at").unwrap_or_else(Utc::now),
+        updated_at: timestamp(value, "updated_at").unwrap_or_else(Utc::now),
+        name: string(value, "name"),
+        rows: value["rows"].as_i64(),
+        description: optional_string(value, "description"),
+        entity_name: optional_string(value, "entity_name"),
+        entity_type: optional_string(value, "entity_type"),
+        active: boolean(value, "active"),
+        db_id: integer(value, "db_id")?,
+        display_name: optional_string(value, "display_name"),
+        visibility_type: optional_string(value, "visibility_type"),
+        schema: optional_string(value, "schema"),
+        points_of_interest: optional_string(value, "points_of_interest"),
+        caveats: optional_string(value, "caveats"),
+        show_in_getting_started: boolean(value, "show_in_getting_started"),
+        fields_hash: optional_string(value, "fields_hash"),
+    })
+}
+
+fn field(value: &Value) -> Result<DbField, Box<dyn Error>> {
+    Ok(DbField {
+        id: id(value)?,
+        created_at: timestamp(value, "created_at").unwrap_or_else(Utc::now),
+        updated_at: timestamp(value, "updated_at").unwrap_or_else(Utc::now),
+        name: string(value, "name"),
+        base_type: string(value, "base_type"),
+        // Newer versions of metabase renamed `special_type` to `semantic_type`
+        special_type: optional_string(value, "special_type").or_else(|| optional_string(value, "semantic_type")),
+        active: boolean(value, "active"),
+        description: optional_string(value, "description"),
+        preview_display: boolean(value, "preview_display"),
+        position: optional_integer(value, "position").unwrap_or(0),
+        table_id: integer(value, "table_id")?,
+        parent_id: optional_integer(value, "parent_id"),
+        display_name: optional_string(value, "display_name"),
+        visibility_type: string(value, "visibility_type"),
+        fk_target_field_id: optional_integer(value, "fk_target_field_id"),
+        last_analyzed: timestamp(value, "last_analyzed"),
+        points_of_interest: optional_string(value, "points_of_interest"),
+        caveats: optional_string(value, "caveats"),
+        fingerprint: optional_string(value, "fingerprint"),
+        fingerprint_version: optional_integer(value, "fingerprint_version").unwrap_or(0),
+        database_type: string(value, "database_type"),
+        has_field_values: optional_string(value, "has_field_values"),
+        settings: optional_string(value, "settings"),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+
+    /// A request received by the test server: its method, path and json body.
+    type Request = (String, String, Value);
+
+    /// Serves canned responses by method and path, recording the requests. A response listed more than once for the
+    /// same request is used once, in order (the last one is reused).
+    fn serve(responses: Vec<(&'static str, &'static str, Value)>) -> (Client, Arc<Mutex<Vec<Request>>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let recorded = requests.clone();
+        std::thread::spawn(move || {
+            let mut responses = responses;
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                let mut parts = line.split_whitespace();
+                let (method, path) = (parts.next().unwrap().to_string(), parts.next().unwrap().to_string());
+                let mut length = 0;
+                loop {
+                    let mut header = String::new();
+                    reader.read_line(&mut header).unwrap();
+                    if header.trim().is_empty() {
+                        break;
+                    }
+                    if let Some((name, value)) = header.split_once(':') {
+                        if name.eq_ignore_ascii_case("content-length") {
+                            length = value.trim().parse().unwrap();
+                        }
+                    }
+                }
+                let mut body = vec![0; length];
+                reader.read_exact(&mut body).unwrap();
+                let body = serde_json::from_slice(&body).unwrap_or(Value::Null);
+                recorded.lock().unwrap().push((method.clone(), path.clone(), body));
+
+                let matching = responses.iter().filter(|(m, p, _)| *m == method && *p == path).count();
+                let index = responses.iter().position(|(m, p, _)| *m == method && *p == path);
+                let (status, response) = match index {
+                    Some(index) if matching > 1 => ("200 OK", responses.remove(index).2),
+                    Some(index) => ("200 OK", responses[index].2.clone()),
+                    None => ("404 Not Found", json!({})),
+                };
+                let response = response.to_string();
+                write!(stream, "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}", status, response.len(), response).unwrap();
+            }
+        });
+        (Client { url, auth: ("x-api-key", "test".to_string()) }, requests)
+    }
+
+    fn requests(requests: &Arc<Mutex<Vec<Request>>>, method: &str) -> Vec<Request> {
+        requests.lock().unwrap().iter().filter(|x| x.0 == method).cloned().collect()
+    }
+
+    fn root() -> ImportOptions {
+        ImportOptions { into_collection: Some(Vec::new()), ..ImportOptions::default() }
+    }
+
+    #[test]
+    fn load_cache_gets_metadata_by_database() {
+        let (client, received) = serve(vec![
+            ("GET", "/api/collection", json!([{ "id": "root", "name": "Our analytics" }, { "id": 1, "name": "Finance", "location": "/" }])),
+            ("GET", "/api/database", json!({ "data": [{ "id": 1, "name": "analytics", "engine": "postgres" }] })),
+            ("GET", "/api/database/1/metadata", json!({
+                "id": 1,
+                "tables": [{ "id": 1, "db_id": 1, "schema": "public", "name": "orders", "fields": [{ "id": 2, "table_id": 1, "name": "total" }] }],
+            })),
+        ]);
+        let cache = client.load_cache().unwrap();
+        assert_eq!(cache.collection(1).unwrap().name, "Finance");
+        assert_eq!(cache.table(1).unwrap().name, "orders");
+        assert_eq!(cache.table_field(1, "total").unwrap().id, 2);
+        let paths = received.lock().unwrap().iter().map(|x| x.1.clone()).collect::<Vec<_>>();
+        assert_eq!(paths, vec!["/api/collection", "/api/database", "/api/database/1/metadata"]);
+    }
+
+    #[test]
+    fn snapshot_question_gets_card() {
+        let (client, _) = serve(vec![
+            ("GET", "/api/card", json!([
+                { "id": 6, "name": "Revenue", "collection_id": null, "archived": true },
+                { "id": 7, "name": "Revenue", "collection_id": null },
+            ])),
+            ("GET", "/api/card/7", json!({
+                "id": 7, "name": "Revenue", "display": "scalar", "creator_id": 1, "database_id": 1, "table_id": 1,
+                "collection_id": null, "cache_ttl": 60, "enable_embedding": true, "embedding_params": { "region": "enabled" },
+                "dataset_query": { "database": 1, "type": "query", "query": { "source-table": 1, "aggregation": [["sum", ["field-id", 2]]] } },
+                "visualization_settings": {},
+            })),
+        ]);
+        let cache = fixtures::cache();
+        let id = commands::find_question_id(&client, &cache, "Revenue").unwrap();
+        assert_eq!(id, 7);
+        let question = commands::snapshot_question(&client, &cache, id).unwrap();
+        assert_eq!(question.name, "Revenue");
+        assert_eq!(question.table.unwrap().table, "orders");
+        assert_eq!(question.cache_ttl, Some(60));
+        assert!(question.query.to_string().contains("snapshot:table:public.orders"));
+        assert!(question.query.to_string().contains("snapshot:field:total"));
+        assert!(question.labels.is_empty());
+        assert_eq!(question.embedding.unwrap().params.unwrap().to_string(), r#"{"region":"enabled"}"#);
+    }
+
+    #[test]
+    fn snapshot_dashboard_gets_cards() {
+        let (client, _) = serve(vec![
+            ("GET", "/api/dashboard/3", json!({
+                "id": 3, "name": "Monthly KPIs", "creator_id": 1, "collection_id": null,
+                "parameters": [{ "id": "abc", "name": "Date", "slug": "date", "type": "date/month-year" }],
+                "dashcards": [
+                    { "id": 10, "dashboard_id": 3, "card_id": 7, "size_x": 4, "size_y": 4, "row": 0, "col": 0,
+                      "parameter_mappings": [{ "card_id": 7, "parameter_id": "abc", "target": ["dimension", ["field-id", 3]] }],
+                      "visualization_settings": {} },
+                    { "id": 11, "dashboard_id": 3, "card_id": null, "sizeX": 4, "sizeY": 2, "row": 4, "col": 0,
+                      "parameter_mappings": [], "visualization_settings": { "text": "Notes" } },
+                ],
+            })),
+            ("GET", "/api/card/7", json!({
+                "id": 7, "name": "Revenue", "display": "scalar", "creator_id": 1, "table_id": 1, "collection_id": null,
+                "dataset_query": { "database": 1, "type": "query", "query": { "source-table": 1 } },
+                "visualization_settings": {},
+            })),
+        ]);
+        let dashboard = commands::snapshot_dashboard(&client, &fixtures::cache(), 3).unwrap();
+        assert_eq!(dashboard.name, "Monthly KPIs");
+        assert_eq!(dashboard.cards.len(), 2);
+        let question = dashboard.cards[0].question.as_ref().unwrap();
+        assert_eq!((question.question.as_str(), question.id), ("Revenue", Some(7)));
+        assert!(dashboard.cards[0].parameters.to_string().contains("snapshot:field:created_at"));
+        assert!(dashboard.cards[1].question.is_none());
+        assert_eq!(dashboard.cards[1].size_y, 2);
+    }
+
+    #[test]
+    fn restore_question_posts_card() {
+        let (client, received) = serve(vec![
+            ("GET", "/api/card", json!([{ "id": 6, "name": "Revenue", "collection_id": 1 }])),
+            ("POST", "/api/card", json!({ "id": 7 })),
+            ("PUT", "/api/card/7", json!({ "id": 7 })),
+        ]);
+        let snapshot = r#"{
+            "name": "Revenue", "display": "scalar", "cache_ttl": 60,
+            "query": {"database": "snapshot:database", "type": "query", "query": {"source-table": "snapshot:table:public.orders", "aggregation": [["sum", "snapshot:field:total"]]}},
+            "settings": {},
+            "database": {"database": "analytics"}, "table": {"table": "orders", "schema": "public"},
+            "labels": [{"slug": "kpi", "name": "KPI"}]
+        }"#;
+        let question = serde_json::from_str(snapshot).unwrap();
+        assert_eq!(commands::restore_question(&client, &fixtures::cache(), question, &root()).unwrap(), 7);
+
+        let posts = requests(&received, "POST");
+        assert_eq!(posts.len(), 1);
+        let body = &posts[0].2;
+        assert_eq!(body["name"], "Revenue");
+        assert_eq!(body["collection_id"], Value::Null);
+        assert_eq!(body["dataset_query"]["database"], 1);
+        assert_eq!(body["dataset_query"]["query"]["source-table"], 1);
+        let puts = requests(&received, "PUT");
+        assert_eq!(puts[0].2, json!({ "cache_ttl": 60, "enable_embedding": false, "embedding_params": null }));
+    }
+
+    #[test]
+    fn restore_question_checks_existing_cards() {
+        let (client, _) = serve(vec![("GET", "/api/card", json!([{ "id": 6, "name": "Revenue", "collection_id": null }]))]);
+        let snapshot = r#"{
+            "name": "Revenue", "display": "scalar",
+            "query": {"database": "snapshot:database", "type": "query", "query": {"source-table": "snapshot:table:public.orders"}},
+            "settings": {}, "table": {"table": "orders", "schema": "public"}
+        }"#;
+        let question = serde_json::from_str(snapshot).unwrap();
+        let err = commands::restore_question(&client, &fixtures::cache(), question, &root()).unwrap_err();
+        assert!(err.to_string().contains("already exists"));
+    }
+
+    #[test]
+    fn restore_dashboard_puts_cards() {
+        let (client, received) = serve(vec![
+            ("GET", "/api/dashboard", json!([])),
+            ("GET", "/api/card", json!([{ "id": 7, "name": "Revenue", "collection_id": null, "table_id": 1 }])),
+            ("POST", "/api/dashboard", json!({ "id": 3 })),
+            ("POST", "/api/dashboard/3/cards", json!({ "id": 10 })),
+            ("POST", "/api/dashboard/3/cards", json!({ "id": 11 })),
+            ("PUT", "/api/dashboard/3/cards", json!({ "status": "ok" })),
+        ]);
+        let snapshot = r#"{
+            "name": "Monthly KPIs",
+            "parameters": [{"id": "abc", "name": "Date", "slug": "date", "type": "date/month-year"}],
+            "cards": [
+                {"size_x": 4, "size_y": 4, "row": 0, "col": 0, "question": {"question": "Revenue"},
+                 "parameters": [{"card_id": "snapshot:question", "parameter_id": "abc", "target": ["dimension", "snapshot:field:created_at"]}],
+                 "settings": {}},
+                {"size_x": 4, "size_y": 2, "row": 4, "col": 0, "parameters": [], "settings": {"virtual_card": {"display": "text"}, "text": "Notes"}}
+            ]
+        }"#;
+        let dashboard = serde_json::from_str(snapshot).unwrap();
+        assert_eq!(commands::restore_dashboard(&client, &fixtures::cache(), dashboard, &root()).unwrap(), 3);
+
+        let posts = requests(&received, "POST");
+        assert_eq!(posts[0].2["parameters"][0]["slug"], "date");
+        assert_eq!(posts[1].2, json!({ "cardId": 7 }));
+        assert_eq!(posts[2].2, json!({ "cardId": null }));
+        let puts = requests(&received, "PUT");
+        let cards = &puts[0].2["cards"];
+        assert_eq!(cards[0]["id"], 10);
+        assert_eq!(cards[0]["card_id"], 7);
+        assert_eq!(cards[0]["parameter_mappings"][0]["card_id"], 7);
+        assert_eq!(cards[0]["parameter_mappings"][0]["target"][1], json!(["field-id", 3]));
+        assert_eq!(cards[1]["id"], 11);
+        assert_eq!(cards[1]["card_id"], Value::Null);
+        assert_eq!(cards[1]["visualization_settings"]["text"], "Notes");
+    }
+}
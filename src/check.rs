@@ -0,0 +1,172 @@
+use std::collections::BTreeMap;
+use std::error::Error;
+
+use diesel::prelude::*;
+use diesel::pg::PgConnection;
+
+use crate::model::*;
+use crate::references;
+use crate::schema::*;
+
+/// Field visibility types which hide a field from the query builder.
+const HIDDEN_FIELD_VISIBILITY: &[&str] = &["sensitive", "retired"];
+
+struct Checker<'a> {
+    cache: &'a Cache,
+    questions: BTreeMap<i32, Question>,
+    segments: BTreeMap<i32, Segment>,
+    metrics: BTreeMap<i32, Metric>,
+}
+
+/// Checks every question, segment and metric for references to missing, inactive or hidden tables and fields.
+pub fn check(conn: &PgConnection) -> Result<(), Box<dyn Error>> {
+    let cache = Cache::load(conn)?;
+    let checker = Checker {
+        cache: &cache,
+        questions: report_card::table
+            .get_results::<Question>(conn)?
+            .into_iter()
+            .map(|row| (row.id, row))
+            .collect(),
+        segments: segment::table
+            .select(Segment::COLUMNS)
+            .get_results::<Segment>(conn)?
+            .into_iter()
+            .map(|row| (row.id, row))
+            .collect(),
+        metrics: metric::table
+            .select(Metric::COLUMNS)
+            .get_results::<Metric>(conn)?
+            .into_iter()
+            .map(|row| (row.id, row))
+            .collect(),
+    };
+
+    let mut broken = 0;
+    for question in checker.questions.values().filter(|x| !x.archived) {
+        let mut problems = Vec::new();
+        if let Err(err) = serde_json::from_str::<serde_json::Value>(&question.dataset_query) {
+            problems.push(format!("query is not valid json ({})", err));
+        }
+        if let Some(database_id) = question.database_id {
+            if checker.cache.database(database_id).is_err() {
+                problems.push(format!("database {} doesn't exist", database_id));
+            }
+        }
+        checker.check_query(&question.dataset_query, &mut problems);
+        checker.check_fields(&question.visualization_settings, &mut problems);
+        broken += report("question", question.id, &question.name, &problems);
+    }
+    for segment in checker.segments.values().filter(|x| !x.archived) {
+        let mut problems = Vec::new();
+        checker.check_table(segment.table_id, &mut problems);
+        checker.check_query(&segment.definition, &mut problems);
+        broken += report("segment", segment.id, &segment.name, &problems);
+    }
+    for metric in checker.metrics.values().filter(|x| !x.archived) {
+        let mut problems = Vec::new();
+        checker.check_table(metric.table_id, &mut problems);
+        checker.check_query(&metric.definition, &mut problems);
+        broken += report("metric", metric.id, &metric.name, &problems);
+    }
+
+    if broken > 0 {
+        return Err(crate::error!("found {} broken references", broken));
+    }
+    eprintln!("no broken references found");
+    Ok(())
+}
+
+fn report(kind: &str, id: i32, name: &str, problems: &[String]) -> usize {
+    // The same field is often referenced by both the query and the visualization settings
+    let mut reported = Vec::new();
+    for problem in problems {
+        if !reported.contains(&problem) {
+            println!("{} {} ({}): {}", kind, id, name, problem);
+            reported.push(problem);
+        }
+    }
+    reported.len()
+}
+
+impl<'a> Checker<'a> {
+    fn check_query(&self, raw: &str, problems: &mut Vec<String>) {
+        for table_id in references::table_ids(raw) {
+            self.check_table(table_id, problems);
+        }
+        for question_id in references::question_ids(raw) {
+            match self.questions.get(&question_id) {
+                None => problems.push(format!("source question {} doesn't exist", question_id)),
+                Some(question) if question.archived => {
+                    problems.push(format!("source question {} ({}) is archived", question_id, question.name));
+                }
+                Some(_) => (),
+            }
+        }
+        for segment_id in references::segment_ids(raw) {
+            match self.segments.get(&segment_id) {
+                None => problems.push(format!("segment {} doesn't exist", segment_id)),
+                Some(segment) if segment.archived => {
+                    problems.push(format!("segment {} ({}) is archived", segment_id, segment.name));
+                }
+                Some(_) => (),
+            }
+        }
+        for metric_id in references::metric_ids(raw) {
+            match self.metrics.get(&metric_id) {
+                None => problems.push(format!("metric {} doesn't exist", metric_id)),
+                Some(metric) if metric.archived => {
+                    problems.push(format!("metric {} ({}) is archived", metric_id, metric.name));
+                }
+                Some(_) => (),
+            }
+        }
+        self.check_fields(raw, problems);
+    }
+
+    fn check_table(&self, table_id: i32, problems: &mut Vec<String>) {
+        let table = match self.cache.table(table_id) {
+            Ok(table) => table,
+            Err(_) => return problems.push(format!("table {} doesn't exist", table_id)),
+        };
+        let name = format!("{}.{}", table.schema_or("public"), table.name);
+        if !table.active {
+            problems.push(format!("table {} ({}) is inactive", table_id, name));
+        } else if let Some(visibility) = &table.visibility_type {
+            problems.push(format!("table {} ({}) is hidden ({})", table_id, name, visibility));
+        }
+    }
+
+    fn check_fields(&self, raw: &str, problems: &mut Vec<String>) {
+        for field_id in references::field_ids(raw) {
+            let field = match self.cache.field(field_id) {
+                Ok(field) => field,
+                Err(_) => {
+                    problems.push(format!("field {} doesn't exist", field_id));
+                    continue;
+                }
+            };
+            let name = match self.cache.table(field.table_id) {
+                Ok(table) => format!("{}.{}.{}", table.schema_or("public"), table.name, field.name),
+                Err(_) => field.name.clone(),
+            };
+            if !field.active {
+                problems.push(format!("field {} ({}) is inactive", field_id, name));
+            } else if HIDDEN_FIELD_VISIBILITY.contains(&field.visibility_type.as_str()) {
+                problems.push(format!("field {} ({}) is hidden ({})", field_id, name, field.visibility_type));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn report_counts_each_problem_once() {
+        let problems = vec!["field 3 doesn't exist".to_string(), "table 1 is inactive".to_string(), "field 3 doesn't exist".to_string()];
+        assert_eq!(report("question", 1, "Revenue", &problems), 2);
+        assert_eq!(report("question", 1, "Revenue", &[]), 0);
+    }
+}
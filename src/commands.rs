@@ -1,3 +1,4 @@
+use std::collections::BTreeMap;
 use std::error::Error;
 
 use chrono::Utc;
@@ -6,230 +7,249 @@ use diesel::prelude::*;
 use diesel::pg::PgConnection;
 use regex::{Captures, Regex};
 
+use crate::mapping::NameMap;
 use crate::model::*;
 use crate::schema::*;
+use crate::secrets;
 use crate::snapshot::*;
+use crate::users;
 
-pub fn import(database_url: &str, filename: &str) -> Result<(), Box<dyn Error>> {
-    let conn = PgConnection::establish(&database_url)?;
-    let cache = Cache::load(&conn)?;
+pub const DEFAULT_COLLECTION_COLOR: &str = "#509EE3";
+
+/// Settings which identify (or are maintained by) a particular instance, so they're only exported when included explicitly.
+const INSTANCE_SETTINGS: &[&str] = &[
+    "analytics-uuid",
+    "instance-creation",
+    "settings-last-updated",
+    "setup-token",
+    "site-uuid",
+    "version-info",
+    "version-info-last-checked",
+];
+
+#[derive(Clone, Default)]
+pub struct ImportOptions {
+    /// Renames applied to database and schema dependencies before they are resolved
+    pub mappings: NameMap,
+
+    /// The collection path to import into, instead of the snapshot's collection (empty for the root collection)
+    pub into_collection: Option<Vec<String>>,
+
+    /// Whether missing collections should be created (rather than failing the import)
+    pub create_collections: bool,
+
+    /// Whether embedding (and the embedded parameters' config) is imported, rather than disabled
+    pub with_embedding: bool,
+
+    /// What happens to the public links of imported questions and dashboards
+    pub public_uuid: PublicUuid,
+
+    /// The email of the user the imported items are created by (defaults to the first administrator)
+    pub creator: Option<String>,
+}
+
+/// How the public link (`public_uuid`) of an imported question or dashboard is set.
+#[derive(Clone, Copy, PartialEq)]
+pub enum PublicUuid {
+    /// The item isn't shared publicly
+    Drop,
+
+    /// The item keeps the public link from the snapshot (so existing links keep working)
+    Preserve,
+
+    /// The item gets a new public link, if it was shared publicly
+    Generate,
+}
+
+// Deriving needs `#[default]` on the variant, which older compilers don't support
+#[allow(clippy::derivable_impls)]
+impl Default for PublicUuid {
+    fn default() -> Self {
+        PublicUuid::Drop
+    }
+}
+
+impl std::str::FromStr for PublicUuid {
+    type Err = Box<dyn Error>;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "drop" => Ok(PublicUuid::Drop),
+            "preserve" => Ok(PublicUuid::Preserve),
+            "generate" => Ok(PublicUuid::Generate),
+            _ => Err(crate::error!("unknown public uuid mode `{}` (expected drop, preserve or generate)", value)),
+        }
+    }
+}
+
+/// Splits a collection path like "Finance/Reports" into its collection names.
+pub fn parse_collection_path(path: &str) -> Vec<String> {
+    path.split('/').map(|x| x.trim()).filter(|x| !x.is_empty()).map(|x| x.to_string()).collect()
+}
+
+pub fn import(conn: &PgConnection, filename: &str, options: &ImportOptions) -> Result<(), Box<dyn Error>> {
+    let cache = Cache::load(conn)?;
     let snapshot_bytes = std::fs::read(filename)?;
     let snapshot: Snapshot = serde_json::from_slice(&snapshot_bytes)?;
     match snapshot {
         Snapshot::Dashboard(dashboard) => {
-            let collection_id = if let Some(collection_dep) = &dashboard.collection {
-                let collection = cache.search_collections(&collection_dep.collection)
-                    .into_iter()
-                    .filter(|x| !x.archived)
-                    .next()
-                    .ok_or(crate::error!("collection doesn't exist (collection: {})", collection_dep.collection))?;
-                let already_exists: bool = 
-                    diesel::select(
-                        diesel::dsl::exists(
-                            report_dashboard::table
-                                .filter(report_dashboard::name.eq(&dashboard.name))
-                                .filter(report_dashboard::collection_id.eq(collection.id))
-                        )
-                    )
-                    .get_result(&conn)?;
-                if already_exists {
-                    return Err(crate::error!("a dashboard with the same name and collection already exists"));
-                }
-                Some(collection.id)
-            } else {
-                let already_exists: bool = 
-                    diesel::select(
-                        diesel::dsl::exists(
-                            report_dashboard::table
-                                .filter(report_dashboard::name.eq(&dashboard.name))
-                                .filter(report_dashboard::collection_id.is_null())
-                        )
-                    )
-                    .get_result(&conn)?;
-                if already_exists {
-                    return Err(crate::error!("a dashboard with the same name and collection already exists"));
-                }
-                None
-            };
-
-
-            let parameters = dashboard.parameters.to_string();
-            let timestamp = Utc::now();
             conn.transaction::<_, Box<dyn Error>, _>(|| {
-                let dashboard_id = 
-                    diesel::insert_into(report_dashboard::table)
-                        .values((
-                            report_dashboard::name.eq(dashboard.name),
-                            report_dashboard::description.eq(dashboard.description),
-                            report_dashboard::creator_id.eq(1),
-                            report_dashboard::created_at.eq(timestamp),
-                            report_dashboard::updated_at.eq(timestamp),
-                            report_dashboard::parameters.eq(parameters),
-                            report_dashboard::show_in_getting_started.eq(false),
-                            report_dashboard::enable_embedding.eq(false),
-                            report_dashboard::archived.eq(false),
-                            report_dashboard::collection_id.eq(collection_id),
-                        ))
-                        .returning(report_dashboard::id)
-                        .get_result::<i32>(&conn)?;
-                for card in dashboard.cards {
-                    if let Some(question) = card.question {
-                        let question = if let Some(collection) = &question.collection {
-                            let collection = cache.search_collections(collection)
-                                .into_iter()
-                                .filter(|x| !x.archived)
-                                .next()
-                                .ok_or(crate::error!("collection doesn't exist (collection: {})", collection))?;
-                            report_card::table
-                                .filter(report_card::name.eq(&question.question))
-                                .filter(report_card::collection_id.eq(collection.id))
-                                .get_result::<Question>(&conn)
-                                .optional()?
-                                .ok_or(crate::error!("question doesn't exist (question: {})", question.question))?
-                        } else {
-                            report_card::table
-                                .filter(report_card::name.eq(&question.question))
-                                .filter(report_card::collection_id.is_null())
-                                .get_result::<Question>(&conn)
-                                .optional()?
-                                .ok_or(crate::error!("question doesn't exist (question: {})", question.question))?
-                        };
-                        let table_id = question.table_id.ok_or(crate::error!("cannot restore card (unknown question table)"))?;
-                        let table = cache.table(table_id)?;
-                        let parameters = restore_parameters(&cache, &question, &table, card.parameters)?;
-                        let settings = restore_settings(&cache, &table, card.settings)?;
-                        diesel::insert_into(report_dashboardcard::table)
-                            .values((
-                                report_dashboardcard::dashboard_id.eq(dashboard_id),
-                                report_dashboardcard::created_at.eq(timestamp),
-                                report_dashboardcard::updated_at.eq(timestamp),
-                                report_dashboardcard::sizeX.eq(card.size_x),
-                                report_dashboardcard::sizeY.eq(card.size_y),
-                                report_dashboardcard::row.eq(card.row),
-                                report_dashboardcard::col.eq(card.col),
-                                report_dashboardcard::card_id.eq(question.id),
-                                report_dashboardcard::parameter_mappings.eq(parameters),
-                                report_dashboardcard::visualization_settings.eq(settings),
-                            ))
-                            .execute(&conn)?;
-                    }
-                }
+                restore_dashboard(conn, &cache, dashboard, options)?;
                 Ok(())
             })?;
         }
-        Snapshot::Question(question) => {
-            let collection_dep = question.collection
-                .ok_or(crate::error!("cannot restore question (missing collection dependency)"))?;
-            let collection = cache.search_collections(&collection_dep.collection)
-                .into_iter()
-                .filter(|x| !x.archived)
-                .next()
-                .ok_or(crate::error!("collection doesn't exist (collection: {})", collection_dep.collection))?;
-            let already_exists: bool = 
-                diesel::select(
-                    diesel::dsl::exists(
-                        report_card::table
-                            .filter(report_card::name.eq(&question.name))
-                            .filter(report_card::collection_id.eq(collection.id))
-                    )
-                )
-                .get_result(&conn)?;
-            if already_exists {
-                return Err(crate::error!("a question with the same name and collection already exists"));
+        Snapshot::Database(database) => {
+            if !cache.search_databases(&database.name).is_empty() {
+                return Err(crate::error!("a database with the same name already exists"));
             }
-
-            let table_dep = question.table
-                .ok_or(crate::error!("cannot restore question (missing table dependency)"))?;
-            let table = discover_table(&cache, &table_dep, &question.database)
-                .ok_or(crate::error!("table doesn't exist (tablename: {}.{})", table_dep.schema_or("public"), &table_dep.table))?;
-            let query = restore_query(&cache, &table, question.query)?;
-            let settings = restore_settings(&cache, &table, question.settings)?;
+            let details = database.details.map(|x| secrets::substitute(&x.to_string(), &secrets::env)).transpose()?;
+            let options = database.options.map(|x| x.to_string());
             let timestamp = Utc::now();
-            diesel::insert_into(report_card::table)
+            diesel::insert_into(metabase_database::table)
                 .values((
-                    report_card::name.eq(question.name),
-                    report_card::description.eq(question.description),
-                    report_card::display.eq(question.display),
-                    report_card::dataset_query.eq(query),
-                    report_card::visualization_settings.eq(settings),
-                    report_card::creator_id.eq(1),
-                    report_card::created_at.eq(timestamp),
-                    report_card::updated_at.eq(timestamp),
-                    report_card::database_id.eq(table.db_id),
-                    report_card::table_id.eq(table.id),
-                    report_card::query_type.eq("query"),
-                    report_card::archived.eq(false),
-                    report_card::collection_id.eq(collection.id),
-                    report_card::enable_embedding.eq(false),
+                    metabase_database::name.eq(database.name),
+                    metabase_database::description.eq(database.description),
+                    metabase_database::created_at.eq(timestamp),
+                    metabase_database::updated_at.eq(timestamp),
+                    metabase_database::details.eq(details),
+                    metabase_database::engine.eq(database.engine),
+                    metabase_database::is_sample.eq(false),
+                    metabase_database::is_full_sync.eq(database.is_full_sync),
+                    metabase_database::metadata_sync_schedule.eq(database.metadata_sync_schedule),
+                    metabase_database::cache_field_values_schedule.eq(database.cache_field_values_schedule),
+                    metabase_database::timezone.eq(database.timezone),
+                    metabase_database::is_on_demand.eq(database.is_on_demand),
+                    metabase_database::options.eq(options),
                 ))
-                .execute(&conn)?;
+                .execute(conn)?;
         }
+        Snapshot::Question(question) => {
+            conn.transaction::<_, Box<dyn Error>, _>(|| {
+                restore_question(conn, &cache, question, options)?;
+                Ok(())
+            })?;
+        }
+        Snapshot::Settings(settings) => {
+            let values = settings.settings
+                .into_iter()
+                .map(|(key, value)| {
+                    let value = restore_setting(&key, value, &secrets::env)?;
+                    Ok((key, value))
+                })
+                .collect::<Result<Vec<_>, Box<dyn Error>>>()?;
+            conn.transaction::<_, Box<dyn Error>, _>(|| {
+                for (key, value) in &values {
+                    upsert_setting(conn, key, value)?;
+                }
+                // Metabase reloads its cached settings when this changes
+                let timestamp = Utc::now().format("%Y-%m-%d %H:%M:%S%.6f+00").to_string();
+                upsert_setting(conn, "settings-last-updated", &timestamp)?;
+                Ok(())
+            })?;
+            eprintln!("imported {} settings", values.len());
+        }
+    }
+    Ok(())
+}
+
+/// Substitutes the `${ENV_VAR}` placeholders that export writes for secret settings (other values, e.g. templates,
+/// can contain `${...}` themselves, so they're imported as they are).
+fn restore_setting(key: &str, value: String, env: secrets::Env) -> Result<String, Box<dyn Error>> {
+    if secrets::is_secret(key) {
+        secrets::substitute_plain(&value, env)
+    } else {
+        Ok(value)
+    }
+}
+
+/// Replaces the value of a secret setting with an `${MB_<KEY>}` placeholder (e.g. `${MB_EMAIL_SMTP_PASSWORD}`).
+fn redact_setting(key: &str, value: String) -> String {
+    if secrets::is_secret(key) && !value.is_empty() {
+        secrets::placeholder(&secrets::env_name(&["MB", key]))
+    } else {
+        value
     }
+}
+
+fn upsert_setting(conn: &PgConnection, key: &str, value: &str) -> Result<(), Box<dyn Error>> {
+    diesel::insert_into(setting::table)
+        .values((setting::key.eq(key), setting::value.eq(value)))
+        .on_conflict(setting::key)
+        .do_update()
+        .set(setting::value.eq(value))
+        .execute(conn)?;
     Ok(())
 }
 
-pub fn export(database_url: &str, source: &str, id: &str) -> Result<(), Box<dyn Error>> {
-    let conn = PgConnection::establish(&database_url)?;
-    let cache = Cache::load(&conn)?;
+/// Snapshots the instance settings, filtered by the include/exclude patterns and with secrets redacted.
+fn snapshot_settings_table(conn: &PgConnection, options: &ExportOptions) -> Result<SettingsSnapshot, Box<dyn Error>> {
+    let matches = |patterns: &[String], key: &str| {
+        patterns.iter().any(|pattern| {
+            let re = format!("^{}$", regex::escape(pattern).replace(r"\*", ".*"));
+            Regex::new(&re).map(|re| re.is_match(key)).unwrap_or(false)
+        })
+    };
+    let mut settings = BTreeMap::new();
+    for (key, value) in setting::table.order(setting::key).get_results::<(String, String)>(conn)? {
+        let included = if options.include.is_empty() {
+            !INSTANCE_SETTINGS.contains(&key.as_str())
+        } else {
+            matches(&options.include, &key)
+        };
+        if !included || matches(&options.exclude, &key) {
+            continue;
+        }
+        let value = redact_setting(&key, value);
+        settings.insert(key, value);
+    }
+    Ok(SettingsSnapshot { settings })
+}
+
+#[derive(Default)]
+pub struct ExportOptions {
+    /// Only export the settings whose keys match one of these patterns (e.g. `email-*`)
+    pub include: Vec<String>,
+
+    /// Don't export the settings whose keys match one of these patterns
+    pub exclude: Vec<String>,
+}
+
+pub fn export(conn: &PgConnection, source: &str, id: Option<&str>, options: &ExportOptions) -> Result<(), Box<dyn Error>> {
+    let cache = Cache::load(conn)?;
+    if source == "settings" {
+        let snapshot = Snapshot::Settings(snapshot_settings_table(conn, options)?);
+        println!("{}", serde_json::to_string(&snapshot)?);
+        return Ok(());
+    }
+    let id = id.ok_or(crate::error!("the {} to export must be given", source))?;
     match source {
         "question" => {
-            let id: i32 = id.parse()?;
-            let question = report_card::table.find(id).get_result::<Question>(&conn)?;
-            let collection = question.collection_id.map(|id| cache.collection(id)).transpose()?;
-            let database = question.database_id.map(|id| cache.database(id)).transpose()?;
-            let table = question.table_id.map(|id| cache.table(id)).transpose()?;
-            let snapshot = Snapshot::Question(QuestionSnapshot {
-                name: question.name,
-                description: question.description,
-                display: question.display,
-                query: snapshot_query(&cache, &question.dataset_query)?,
-                settings: snapshot_settings(&cache, &question.visualization_settings)?,
-                collection: collection.map(|col| CollectionDependency { collection: col.name.clone() }),
-                database: database.map(|db| DatabaseDependency { database: db.name.clone() }),
-                table: table.map(|tbl| TableDependency { table: tbl.name.clone(), schema: tbl.schema.clone() }),
-            });
+            let id = find_question_id(conn, &cache, id)?;
+            let snapshot = Snapshot::Question(snapshot_question(conn, &cache, id)?);
             println!("{}", serde_json::to_string(&snapshot)?);
         }
         "dashboard" => {
-            let id: i32 = id.parse()?;
-            let dashboard = report_dashboard::table.find(id).get_result::<Dashboard>(&conn)?;
-            let collection = dashboard.collection_id.map(|id| cache.collection(id)).transpose()?;
-            let cards = report_dashboardcard::table
-                .filter(report_dashboardcard::dashboard_id.eq(id))
-                .get_results::<DashboardCard>(&conn)?;
-            let mut card_snapshots = Vec::new();
-            for card in cards {
-                let question = if let Some(question_id) = card.card_id {
-                    let question = report_card::table.find(question_id).get_result::<Question>(&conn)?;
-                    let collection = question.collection_id.map(|id| cache.collection(id)).transpose()?;
-                    Some(QuestionDependency {
-                        question: question.name,
-                        collection: collection.map(|col| col.name.clone()),
-                    })
-                } else {
-                    None
-                };
-                card_snapshots.push(CardSnapshot {
-                    size_x: card.size_x,
-                    size_y: card.size_y,
-                    row: card.row,
-                    col: card.col,
-                    question,
-                    parameters: snapshot_paremeters(&cache, &card.parameter_mappings)?,
-                    settings: snapshot_settings(&cache, &card.visualization_settings)?,
-                });
-            }
-            let snapshot = Snapshot::Dashboard(DashboardSnapshot {
-                name: dashboard.name,
-                description: dashboard.description,
-                parameters: SerializedSnapshot::new(&dashboard.parameters)?,
-                collection: collection.map(|col| CollectionDependency { collection: col.name.clone() }),
-                cards: card_snapshots,
+            let id = find_dashboard_id(conn, &cache, id)?;
+            let snapshot = Snapshot::Dashboard(snapshot_dashboard(conn, &cache, id)?);
+            println!("{}", serde_json::to_string(&snapshot)?);
+        }
+        "database" => {
+            let database = find_database(&cache, id)?;
+            let snapshot = Snapshot::Database(DatabaseSnapshot {
+                name: database.name.clone(),
+                description: database.description.clone(),
+                engine: database.engine.clone(),
+                details: database.details.as_ref().map(|raw| snapshot_details(database, raw)).transpose()?,
+                metadata_sync_schedule: database.metadata_sync_schedule.clone(),
+                cache_field_values_schedule: database.cache_field_values_schedule.clone(),
+                timezone: database.timezone.clone(),
+                options: database.options.as_ref().map(|raw| SerializedSnapshot::new(raw)).transpose()?,
+                is_full_sync: database.is_full_sync,
+                is_on_demand: database.is_on_demand,
             });
             println!("{}", serde_json::to_string(&snapshot)?);
         }
         // "collection" => { ... }
-        // "database" => { ... }
         // "table" => { ... }
         // "table-segments" => { ... }
         // "table-metrics" => { ... }
@@ -240,26 +260,927 @@ pub fn export(database_url: &str, source: &str, id: &str) -> Result<(), Box<dyn
     Ok(())
 }
 
-fn discover_table<'a>(cache: &'a Cache, tbl: &TableDependency, db: &Option<DatabaseDependency>) -> Option<&'a DbTable> {
-    let database = db.as_ref().and_then(|db| cache.search_databases(&db.database).into_iter().next());
-    let tables = cache.search_tables(&tbl.table, &tbl.schema_or("public"));
-    if let Some(database) = database {
-        if let Some(table) = tables.iter().find(|x| x.db_id == database.id) {
-            return Some(table);
+pub fn snapshot_question<S: ExportSource>(source: &S, cache: &Cache, id: i32) -> Result<QuestionSnapshot, Box<dyn Error>> {
+    let question = source.load_question(id)?;
+    let collection = question.collection_id.map(|id| cache.collection(id)).transpose()?;
+    let database = question.database_id.map(|id| cache.database(id)).transpose()?;
+    let table = question.table_id.map(|id| cache.table(id)).transpose()?;
+    let labels = source.question_labels(question.id)?;
+    let result_metadata = match &question.result_metadata {
+        Some(raw) => snapshot_result_metadata(cache, &question.name, raw)?,
+        None => None,
+    };
+    Ok(QuestionSnapshot {
+        name: question.name,
+        description: question.description,
+        display: question.display,
+        query: snapshot_query(cache, &question.dataset_query)?,
+        settings: snapshot_settings(cache, &question.visualization_settings)?,
+        collection: collection.map(|col| collection_dependency(cache, col)).transpose()?,
+        database: database.map(|db| DatabaseDependency { database: db.name.clone() }),
+        table: table.map(|tbl| TableDependency { table: tbl.name.clone(), schema: tbl.schema.clone() }),
+        labels: labels.into_iter().map(|x| LabelDependency { slug: x.slug, name: x.name, icon: x.icon }).collect(),
+        cache_ttl: question.cache_ttl,
+        collection_position: question.collection_position,
+        result_metadata,
+        embedding: snapshot_embedding(question.enable_embedding, &question.embedding_params)?,
+        public_uuid: question.public_uuid,
+    })
+}
+
+pub fn snapshot_dashboard<S: ExportSource>(source: &S, cache: &Cache, id: i32) -> Result<DashboardSnapshot, Box<dyn Error>> {
+    let dashboard = source.load_dashboard(id)?;
+    let collection = dashboard.collection_id.map(|id| cache.collection(id)).transpose()?;
+    let cards = source.dashboard_cards(id)?;
+    let mut card_snapshots = Vec::new();
+    for card in cards {
+        let question = if let Some(question_id) = card.card_id {
+            let question = source.load_question(question_id)?;
+            let collection = question.collection_id.map(|id| cache.collection(id)).transpose()?;
+            let collection_dep = collection.map(|col| collection_dependency(cache, col)).transpose()?;
+            Some(QuestionDependency {
+                question: question.name,
+                collection: collection_dep.as_ref().map(|dep| dep.collection.clone()),
+                collection_ancestors: collection_dep.map(|dep| dep.ancestors).unwrap_or_default(),
+                id: Some(question.id),
+            })
+        } else {
+            None
+        };
+        card_snapshots.push(CardSnapshot {
+            size_x: card.size_x,
+            size_y: card.size_y,
+            row: card.row,
+            col: card.col,
+            question,
+            parameters: snapshot_paremeters(cache, &card.parameter_mappings)?,
+            settings: snapshot_settings(cache, &card.visualization_settings)?,
+        });
+    }
+    Ok(DashboardSnapshot {
+        name: dashboard.name,
+        description: dashboard.description,
+        parameters: SerializedSnapshot::new(&dashboard.parameters)?,
+        collection: collection.map(|col| collection_dependency(cache, col)).transpose()?,
+        cards: card_snapshots,
+        collection_position: dashboard.collection_position,
+        embedding: snapshot_embedding(dashboard.enable_embedding, &dashboard.embedding_params)?,
+        public_uuid: dashboard.public_uuid,
+    })
+}
+
+fn snapshot_embedding(enabled: bool, params: &Option<String>) -> Result<Option<EmbeddingSnapshot>, Box<dyn Error>> {
+    if !enabled && params.is_none() {
+        return Ok(None);
+    }
+    let params = params.as_ref().map(|x| SerializedSnapshot::new(x)).transpose()?;
+    Ok(Some(EmbeddingSnapshot { enabled, params }))
+}
+
+/// Where exported questions and dashboards are read from: the application database, or metabase's api (`api::Client`),
+/// which works whatever database metabase itself uses (e.g. mysql/mariadb).
+pub trait ExportSource {
+    fn load_question(&self, id: i32) -> Result<Question, Box<dyn Error>>;
+
+    /// The labels of a question, ordered by slug.
+    fn question_labels(&self, question_id: i32) -> Result<Vec<Label>, Box<dyn Error>>;
+
+    fn load_dashboard(&self, id: i32) -> Result<Dashboard, Box<dyn Error>>;
+
+    fn dashboard_cards(&self, dashboard_id: i32) -> Result<Vec<DashboardCard>, Box<dyn Error>>;
+
+    /// The (non-archived) questions with a name.
+    fn questions_named(&self, name: &str) -> Result<Candidates, Box<dyn Error>>;
+
+    /// The (non-archived) dashboards with a name.
+    fn dashboards_named(&self, name: &str) -> Result<Candidates, Box<dyn Error>>;
+}
+
+/// The ids of the items with a name, along with their collection (`None` for the root collection).
+pub type Candidates = Vec<(i32, Option<i32>)>;
+
+/// Where imported questions and dashboards are created: the application database, or metabase's api (`api::Client`).
+///
+/// The snapshot is resolved against the cache the same way for both, and the target only looks up and creates rows.
+pub trait ImportTarget {
+    /// Finds (or creates) the collection that an imported dashboard/question is placed in.
+    fn import_collection(&self, cache: &Cache, dep: &Option<CollectionDependency>, options: &ImportOptions) -> Result<Option<i32>, Box<dyn Error>>;
+
+    fn question_exists(&self, name: &str, collection_id: Option<i32>) -> Result<bool, Box<dyn Error>>;
+
+    fn dashboard_exists(&self, name: &str, collection_id: Option<i32>) -> Result<bool, Box<dyn Error>>;
+
+    /// Finds a question by its id.
+    fn question(&self, id: i32) -> Result<ExistingQuestion, Box<dyn Error>>;
+
+    /// Finds a question by its name and collection.
+    fn find_question(&self, name: &str, collection_id: Option<i32>) -> Result<Option<ExistingQuestion>, Box<dyn Error>>;
+
+    fn create_question(&self, question: NewQuestion, options: &ImportOptions) -> Result<i32, Box<dyn Error>>;
+
+    fn create_dashboard(&self, dashboard: NewDashboard, cards: Vec<NewCard>, options: &ImportOptions) -> Result<i32, Box<dyn Error>>;
+}
+
+/// The question a restored dashboard card refers to.
+pub struct ExistingQuestion {
+    pub id: i32,
+    pub table_id: Option<i32>,
+}
+
+/// A question restored from a snapshot, with its tables and fields resolved.
+pub struct NewQuestion {
+    pub name: String,
+    pub description: Option<String>,
+    pub display: ChartType,
+    pub query: String,
+    pub settings: String,
+    pub database_id: i32,
+    pub table_id: i32,
+    pub collection_id: Option<i32>,
+    pub cache_ttl: Option<i32>,
+    pub collection_position: Option<i16>,
+    pub result_metadata: Option<String>,
+    pub enable_embedding: bool,
+    pub embedding_params: Option<String>,
+
+    /// The snapshot's public link, which is kept, replaced or dropped according to `ImportOptions::public_uuid`
+    pub public_uuid: Option<String>,
+
+    pub labels: Vec<LabelDependency>,
+}
+
+/// A dashboard restored from a snapshot (its cards are created along with it).
+pub struct NewDashboard {
+    pub name: String,
+    pub description: Option<String>,
+    pub parameters: String,
+    pub collection_id: Option<i32>,
+    pub collection_position: Option<i16>,
+    pub enable_embedding: bool,
+    pub embedding_params: Option<String>,
+
+    /// The snapshot's public link, which is kept, replaced or dropped according to `ImportOptions::public_uuid`
+    pub public_uuid: Option<String>,
+}
+
+/// A dashboard card restored from a snapshot (text and heading cards have no question).
+pub struct NewCard {
+    pub question_id: Option<i32>,
+    pub size_x: i32,
+    pub size_y: i32,
+    pub row: i32,
+    pub col: i32,
+    pub parameters: String,
+    pub settings: String,
+}
+
+/// Creates a question from a snapshot, returning the new question's id.
+pub fn restore_question<T: ImportTarget>(target: &T, cache: &Cache, question: QuestionSnapshot, options: &ImportOptions) -> Result<i32, Box<dyn Error>> {
+    let table_dep = question.table
+        .ok_or(crate::error!("cannot restore question (missing table dependency)"))?;
+    let table = discover_table(cache, &options.mappings, &table_dep, &question.database)?;
+    let query = restore_query(cache, table, &table_dep, question.query)?;
+    let settings = restore_settings(cache, table, question.settings)?;
+    let result_metadata = match question.result_metadata {
+        Some(metadata) => restore_result_metadata(cache, &options.mappings, table, &question.name, metadata)?,
+        None => None,
+    };
+    let collection_id = target.import_collection(cache, &question.collection, options)?;
+    if target.question_exists(&question.name, collection_id)? {
+        return Err(crate::error!("a question with the same name and collection already exists"));
+    }
+    let query_value: serde_json::Value = serde_json::from_str(&query)?;
+    let template_tags = query_value["native"]["template-tags"].as_object()
+        .map(|tags| tags.keys().cloned().collect::<Vec<_>>())
+        .unwrap_or_default();
+    let (enable_embedding, embedding_params) = restore_embedding(question.embedding, &template_tags, options)?;
+
+    target.create_question(NewQuestion {
+        name: question.name,
+        description: question.description,
+        display: question.display,
+        query,
+        settings,
+        database_id: table.db_id,
+        table_id: table.id,
+        collection_id,
+        cache_ttl: question.cache_ttl,
+        collection_position: question.collection_position,
+        result_metadata,
+        enable_embedding,
+        embedding_params,
+        public_uuid: question.public_uuid,
+        labels: question.labels,
+    }, options)
+}
+
+/// Checks the embedding config of an imported item against its parameter slugs, returning the
+/// `enable_embedding` and `embedding_params` to insert (embedding is disabled unless `--with-embedding` is used).
+pub fn restore_embedding(embedding: Option<EmbeddingSnapshot>, slugs: &[String], options: &ImportOptions) -> Result<(bool, Option<String>), Box<dyn Error>> {
+    let embedding = match embedding {
+        Some(embedding) if options.with_embedding => embedding,
+        _ => return Ok((false, None)),
+    };
+    if let Some(params) = &embedding.params {
+        let params: BTreeMap<String, String> = serde_json::from_str(&params.to_string())?;
+        for (slug, setting) in &params {
+            if !slugs.contains(slug) {
+                return Err(crate::error!("embedding parameter doesn't exist (slug: {})", slug));
+            }
+            match setting.as_str() {
+                "enabled" | "locked" | "disabled" => (),
+                _ => return Err(crate::error!("unknown embedding parameter setting `{}` (slug: {}, expected enabled, locked or disabled)", setting, slug)),
+            }
+        }
+    }
+    Ok((embedding.enabled, embedding.params.map(|x| x.to_string())))
+}
+
+/// Returns the public uuid of an imported item, which must not be used by another question or dashboard.
+fn restore_public_uuid(conn: &PgConnection, public_uuid: Option<String>, options: &ImportOptions) -> Result<Option<String>, Box<dyn Error>> {
+    let public_uuid = match (public_uuid, options.public_uuid) {
+        (Some(public_uuid), PublicUuid::Preserve) => public_uuid,
+        (Some(_), PublicUuid::Generate) => uuid::Uuid::new_v4().to_string(),
+        _ => return Ok(None),
+    };
+    let question_exists: bool = diesel::select(diesel::dsl::exists(
+        report_card::table.filter(report_card::public_uuid.eq(&public_uuid))
+    ))
+    .get_result(conn)?;
+    let dashboard_exists: bool = diesel::select(diesel::dsl::exists(
+        report_dashboard::table.filter(report_dashboard::public_uuid.eq(&public_uuid))
+    ))
+    .get_result(conn)?;
+    if question_exists || dashboard_exists {
+        return Err(crate::error!("the public link is already used (public_uuid: {})", public_uuid));
+    }
+    Ok(Some(public_uuid))
+}
+
+/// Finds a label by its slug, creating it when it doesn't exist.
+fn import_label(conn: &PgConnection, label: &LabelDependency) -> Result<i32, Box<dyn Error>> {
+    let existing = label::table
+        .select(label::id)
+        .filter(label::slug.eq(&label.slug))
+        .get_result::<i32>(conn)
+        .optional()?;
+    match existing {
+        Some(id) => Ok(id),
+        None => {
+            let id = diesel::insert_into(label::table)
+                .values((label::name.eq(&label.name), label::slug.eq(&label.slug), label::icon.eq(&label.icon)))
+                .returning(label::id)
+                .get_result::<i32>(conn)?;
+            Ok(id)
+        }
+    }
+}
+
+/// Creates a dashboard (and its cards) from a snapshot, returning the new dashboard's id.
+pub fn restore_dashboard<T: ImportTarget>(target: &T, cache: &Cache, dashboard: DashboardSnapshot, options: &ImportOptions) -> Result<i32, Box<dyn Error>> {
+    let parameters = dashboard.parameters.to_string();
+    let collection_id = target.import_collection(cache, &dashboard.collection, options)?;
+    if target.dashboard_exists(&dashboard.name, collection_id)? {
+        return Err(crate::error!("a dashboard with the same name and collection already exists"));
+    }
+    let slugs = serde_json::from_str::<Vec<serde_json::Value>>(&parameters)?
+        .iter()
+        .filter_map(|x| x["slug"].as_str().map(|x| x.to_string()))
+        .collect::<Vec<_>>();
+    let (enable_embedding, embedding_params) = restore_embedding(dashboard.embedding, &slugs, options)?;
+
+    let mut cards = Vec::new();
+    for card in dashboard.cards {
+        // Text and heading cards have no question (nor any field references)
+        let (question_id, parameters, settings) = match card.question {
+            Some(question) => {
+                let question = match question.id {
+                    Some(id) => target.question(id)?,
+                    None => {
+                        let question_collection_id = question.collection_path()
+                            .map(|path| cache.find_collection(&path).map(|x| x.id))
+                            .transpose()?;
+                        target.find_question(&question.question, question_collection_id)?
+                            .ok_or(crate::error!("question doesn't exist (question: {})", question.question))?
+                    }
+                };
+                let table_id = question.table_id.ok_or(crate::error!("cannot restore card (unknown question table)"))?;
+                let table = cache.table(table_id)?;
+                let parameters = restore_parameters(cache, question.id, table, card.parameters)?;
+                let settings = restore_settings(cache, table, card.settings)?;
+                (Some(question.id), parameters, settings)
+            }
+            None => (None, card.parameters.to_string(), card.settings.to_string()),
+        };
+        cards.push(NewCard {
+            question_id,
+            size_x: card.size_x,
+            size_y: card.size_y,
+            row: card.row,
+            col: card.col,
+            parameters,
+            settings,
+        });
+    }
+    target.create_dashboard(NewDashboard {
+        name: dashboard.name,
+        description: dashboard.description,
+        parameters,
+        collection_id,
+        collection_position: dashboard.collection_position,
+        enable_embedding,
+        embedding_params,
+        public_uuid: dashboard.public_uuid,
+    }, cards, options)
+}
+
+impl ExportSource for PgConnection {
+    fn load_question(&self, id: i32) -> Result<Question, Box<dyn Error>> {
+        Ok(report_card::table.find(id).get_result::<Question>(self)?)
+    }
+
+    fn question_labels(&self, question_id: i32) -> Result<Vec<Label>, Box<dyn Error>> {
+        let labels = card_label::table
+            .inner_join(label::table)
+            .select(label::all_columns)
+            .filter(card_label::card_id.eq(question_id))
+            .order(label::slug)
+            .get_results::<Label>(self)?;
+        Ok(labels)
+    }
+
+    fn load_dashboard(&self, id: i32) -> Result<Dashboard, Box<dyn Error>> {
+        Ok(report_dashboard::table.find(id).get_result::<Dashboard>(self)?)
+    }
+
+    fn dashboard_cards(&self, dashboard_id: i32) -> Result<Vec<DashboardCard>, Box<dyn Error>> {
+        let cards = report_dashboardcard::table
+            .filter(report_dashboardcard::dashboard_id.eq(dashboard_id))
+            .get_results::<DashboardCard>(self)?;
+        Ok(cards)
+    }
+
+    fn questions_named(&self, name: &str) -> Result<Candidates, Box<dyn Error>> {
+        let questions = report_card::table
+            .select((report_card::id, report_card::collection_id))
+            .filter(report_card::name.eq(name))
+            .filter(report_card::archived.eq(false))
+            .get_results::<(i32, Option<i32>)>(self)?;
+        Ok(questions)
+    }
+
+    fn dashboards_named(&self, name: &str) -> Result<Candidates, Box<dyn Error>> {
+        let dashboards = report_dashboard::table
+            .select((report_dashboard::id, report_dashboard::collection_id))
+            .filter(report_dashboard::name.eq(name))
+            .filter(report_dashboard::archived.eq(false))
+            .get_results::<(i32, Option<i32>)>(self)?;
+        Ok(dashboards)
+    }
+}
+
+impl ImportTarget for PgConnection {
+    fn import_collection(&self, cache: &Cache, dep: &Option<CollectionDependency>, options: &ImportOptions) -> Result<Option<i32>, Box<dyn Error>> {
+        import_collection(self, cache, dep, options)
+    }
+
+    fn question_exists(&self, name: &str, collection_id: Option<i32>) -> Result<bool, Box<dyn Error>> {
+        let exists = if let Some(collection_id) = collection_id {
+            diesel::select(
+                diesel::dsl::exists(
+                    report_card::table
+                        .filter(report_card::name.eq(name))
+                        .filter(report_card::collection_id.eq(collection_id))
+                )
+            )
+            .get_result(self)?
+        } else {
+            diesel::select(
+                diesel::dsl::exists(
+                    report_card::table
+                        .filter(report_card::name.eq(name))
+                        .filter(report_card::collection_id.is_null())
+                )
+            )
+            .get_result(self)?
+        };
+        Ok(exists)
+    }
+
+    fn dashboard_exists(&self, name: &str, collection_id: Option<i32>) -> Result<bool, Box<dyn Error>> {
+        let exists = if let Some(collection_id) = collection_id {
+            diesel::select(
+                diesel::dsl::exists(
+                    report_dashboard::table
+                        .filter(report_dashboard::name.eq(name))
+                        .filter(report_dashboard::collection_id.eq(collection_id))
+                )
+            )
+            .get_result(self)?
+        } else {
+            diesel::select(
+                diesel::dsl::exists(
+                    report_dashboard::table
+                        .filter(report_dashboard::name.eq(name))
+                        .filter(report_dashboard::collection_id.is_null())
+                )
+            )
+            .get_result(self)?
+        };
+        Ok(exists)
+    }
+
+    fn question(&self, id: i32) -> Result<ExistingQuestion, Box<dyn Error>> {
+        let table_id = report_card::table
+            .select(report_card::table_id)
+            .find(id)
+            .get_result::<Option<i32>>(self)?;
+        Ok(ExistingQuestion { id, table_id })
+    }
+
+    fn find_question(&self, name: &str, collection_id: Option<i32>) -> Result<Option<ExistingQuestion>, Box<dyn Error>> {
+        let query = report_card::table
+            .select((report_card::id, report_card::table_id))
+            .filter(report_card::name.eq(name));
+        let question = if let Some(collection_id) = collection_id {
+            query.filter(report_card::collection_id.eq(collection_id)).get_result::<(i32, Option<i32>)>(self).optional()?
+        } else {
+            query.filter(report_card::collection_id.is_null()).get_result::<(i32, Option<i32>)>(self).optional()?
+        };
+        Ok(question.map(|(id, table_id)| ExistingQuestion { id, table_id }))
+    }
+
+    fn create_question(&self, question: NewQuestion, options: &ImportOptions) -> Result<i32, Box<dyn Error>> {
+        let timestamp = Utc::now();
+        let public_uuid = restore_public_uuid(self, question.public_uuid, options)?;
+        let creator_id = users::find_creator(self, options.creator.as_deref())?;
+        let question_id = diesel::insert_into(report_card::table)
+            .values((
+                report_card::name.eq(question.name),
+                report_card::description.eq(question.description),
+                report_card::display.eq(question.display),
+                report_card::dataset_query.eq(question.query),
+                report_card::visualization_settings.eq(question.settings),
+                report_card::creator_id.eq(creator_id),
+                report_card::created_at.eq(timestamp),
+                report_card::updated_at.eq(timestamp),
+                report_card::database_id.eq(question.database_id),
+                report_card::table_id.eq(question.table_id),
+                report_card::query_type.eq("query"),
+                report_card::archived.eq(false),
+                report_card::collection_id.eq(question.collection_id),
+                report_card::cache_ttl.eq(question.cache_ttl),
+                report_card::collection_position.eq(question.collection_position),
+                report_card::result_metadata.eq(question.result_metadata),
+                report_card::enable_embedding.eq(question.enable_embedding),
+                report_card::embedding_params.eq(question.embedding_params),
+                report_card::made_public_by_id.eq(public_uuid.as_ref().map(|_| creator_id)),
+                report_card::public_uuid.eq(public_uuid),
+            ))
+            .returning(report_card::id)
+            .get_result::<i32>(self)?;
+        for label in question.labels {
+            let label_id = import_label(self, &label)?;
+            diesel::insert_into(card_label::table)
+                .values((card_label::card_id.eq(question_id), card_label::label_id.eq(label_id)))
+                .execute(self)?;
+        }
+        Ok(question_id)
+    }
+
+    fn create_dashboard(&self, dashboard: NewDashboard, cards: Vec<NewCard>, options: &ImportOptions) -> Result<i32, Box<dyn Error>> {
+        let timestamp = Utc::now();
+        let public_uuid = restore_public_uuid(self, dashboard.public_uuid, options)?;
+        let creator_id = users::find_creator(self, options.creator.as_deref())?;
+        let dashboard_id =
+            diesel::insert_into(report_dashboard::table)
+                .values((
+                    report_dashboard::name.eq(dashboard.name),
+                    report_dashboard::description.eq(dashboard.description),
+                    report_dashboard::creator_id.eq(creator_id),
+                    report_dashboard::created_at.eq(timestamp),
+                    report_dashboard::updated_at.eq(timestamp),
+                    report_dashboard::parameters.eq(dashboard.parameters),
+                    report_dashboard::show_in_getting_started.eq(false),
+                    report_dashboard::collection_position.eq(dashboard.collection_position),
+                    report_dashboard::enable_embedding.eq(dashboard.enable_embedding),
+                    report_dashboard::embedding_params.eq(dashboard.embedding_params),
+                    report_dashboard::made_public_by_id.eq(public_uuid.as_ref().map(|_| creator_id)),
+                    report_dashboard::public_uuid.eq(public_uuid),
+                    report_dashboard::archived.eq(false),
+                    report_dashboard::collection_id.eq(dashboard.collection_id),
+                ))
+                .returning(report_dashboard::id)
+                .get_result::<i32>(self)?;
+        for card in cards {
+            diesel::insert_into(report_dashboardcard::table)
+                .values((
+                    report_dashboardcard::dashboard_id.eq(dashboard_id),
+                    report_dashboardcard::created_at.eq(timestamp),
+                    report_dashboardcard::updated_at.eq(timestamp),
+                    report_dashboardcard::sizeX.eq(card.size_x),
+                    report_dashboardcard::sizeY.eq(card.size_y),
+                    report_dashboardcard::row.eq(card.row),
+                    report_dashboardcard::col.eq(card.col),
+                    report_dashboardcard::card_id.eq(card.question_id),
+                    report_dashboardcard::parameter_mappings.eq(card.parameters),
+                    report_dashboardcard::visualization_settings.eq(card.settings),
+                ))
+                .execute(self)?;
+        }
+        Ok(dashboard_id)
+    }
+}
+
+pub struct CloneOptions {
+    /// The collection path to place the copy (and the copied questions) in
+    pub to_collection: Vec<String>,
+
+    pub create_collections: bool,
+
+    /// The name of the copy (instead of the original dashboard's name)
+    pub name: Option<String>,
+
+    /// Whether the dashboard's questions are copied too (rather than shared with the original)
+    pub deep: bool,
+
+    /// The email of the user the copies are created by (defaults to the first administrator)
+    pub creator: Option<String>,
+}
+
+#[derive(Serialize)]
+struct CloneResult {
+    dashboard: i32,
+    questions: BTreeMap<i32, i32>,
+}
+
+/// Copies a dashboard (and optionally its questions) by snapshotting and restoring it.
+pub fn clone(conn: &PgConnection, source: &str, identifier: &str, options: &CloneOptions) -> Result<(), Box<dyn Error>> {
+    if source != "dashboard" {
+        return Err(crate::error!("unknown datasource `{}`", source));
+    }
+    let cache = Cache::load(conn)?;
+    let id = find_dashboard_id(conn, &cache, identifier)?;
+    // Cards are restored against their question's table, so questions without one (e.g. native queries) are reported up front
+    let unsupported = report_dashboardcard::table
+        .inner_join(report_card::table)
+        .select((report_card::id, report_card::name))
+        .filter(report_dashboardcard::dashboard_id.eq(id))
+        .filter(report_card::table_id.is_null())
+        .distinct()
+        .order(report_card::id)
+        .get_results::<(i32, String)>(conn)?;
+    if !unsupported.is_empty() {
+        let questions = unsupported.iter().map(|(id, name)| format!("{} (id: {})", name, id)).collect::<Vec<_>>();
+        return Err(crate::error!("can't clone cards of questions without a source table, e.g. native queries (questions: {})", questions.join(", ")));
+    }
+    // Deep copies are all placed in one collection, where questions can't share a name
+    let question_names = report_dashboardcard::table
+        .inner_join(report_card::table)
+        .select((report_card::id, report_card::name))
+        .filter(report_dashboardcard::dashboard_id.eq(id))
+        .distinct()
+        .order(report_card::id)
+        .get_results::<(i32, String)>(conn)?;
+    let duplicates = duplicate_names(&question_names);
+    if options.deep && !duplicates.is_empty() {
+        return Err(crate::error!("can't copy questions with the same name into one collection (questions: {})", duplicates.join(", ")));
+    }
+    let result = conn.transaction::<_, Box<dyn Error>, _>(|| {
+        // The cards keep referring to the original questions (by id, rather than by name)
+        let mut snapshot = snapshot_dashboard(conn, &cache, id)?;
+        if let Some(name) = &options.name {
+            snapshot.name = name.clone();
+        }
+        // The copy isn't pinned in place of the original
+        snapshot.collection_position = None;
+        let import_options = ImportOptions {
+            into_collection: Some(options.to_collection.clone()),
+            create_collections: options.create_collections,
+            creator: options.creator.clone(),
+            ..ImportOptions::default()
+        };
+        let mut questions = BTreeMap::new();
+        if options.deep {
+            for (question_id, _) in &question_names {
+                let mut question = snapshot_question(conn, &cache, *question_id)?;
+                question.collection_position = None;
+                questions.insert(*question_id, restore_question(conn, &cache, question, &import_options)?);
+            }
+            // The cards now refer to the copied questions
+            for question in snapshot.cards.iter_mut().filter_map(|x| x.question.as_mut()) {
+                question.id = question.id.map(|id| questions[&id]);
+            }
+        }
+        let dashboard = restore_dashboard(conn, &cache, snapshot, &import_options)?;
+        Ok(CloneResult { dashboard, questions })
+    })?;
+    println!("{}", serde_json::to_string(&result)?);
+    Ok(())
+}
+
+/// Describes the questions sharing their name with another question.
+fn duplicate_names(questions: &[(i32, String)]) -> Vec<String> {
+    questions.iter()
+        .filter(|(_, name)| questions.iter().filter(|(_, other)| other == name).count() > 1)
+        .map(|(id, name)| format!("{} (id: {})", name, id))
+        .collect()
+}
+
+pub struct RetargetOptions {
+    /// The table the questions currently use, as `<schema>.<table>`
+    pub from_table: String,
+
+    pub from_database: Option<String>,
+
+    /// The table the questions should use instead, as `<schema>.<table>`
+    pub to_table: String,
+
+    pub to_database: Option<String>,
+
+    /// The questions to retarget (by id or path), rather than every question using the source table
+    pub questions: Vec<String>,
+
+    /// Whether to create retargeted copies of the questions (rather than updating them)
+    pub copy: bool,
+
+    /// The collection path to place the copies in (required when copying, as copies next to the originals would
+    /// have the same names)
+    pub into_collection: Option<Vec<String>>,
+
+    pub create_collections: bool,
+
+    /// The email of the user the copies are created by (defaults to the first administrator)
+    pub creator: Option<String>,
+}
+
+/// Points questions at another table, by snapshotting them and restoring the snapshot against the target table.
+pub fn retarget(conn: &PgConnection, options: &RetargetOptions) -> Result<(), Box<dyn Error>> {
+    let cache = Cache::load(conn)?;
+    let source = find_table(&cache, &options.from_table, options.from_database.as_deref())?;
+    let target = find_table(&cache, &options.to_table, options.to_database.as_deref())?;
+    let source_dep = TableDependency { table: source.name.clone(), schema: source.schema.clone() };
+    let questions = if options.questions.is_empty() {
+        report_card::table
+            .filter(report_card::table_id.eq(source.id))
+            .filter(report_card::archived.eq(false))
+            .order(report_card::id)
+            .get_results::<Question>(conn)?
+    } else {
+        let mut questions = Vec::new();
+        for identifier in &options.questions {
+            let id = find_question_id(conn, &cache, identifier)?;
+            let question = report_card::table.find(id).get_result::<Question>(conn)?;
+            if question.table_id != Some(source.id) {
+                return Err(crate::error!("question doesn't use the source table (question: {})", identifier));
+            }
+            questions.push(question);
+        }
+        questions
+    };
+
+    let timestamp = Utc::now();
+    conn.transaction::<_, Box<dyn Error>, _>(|| {
+        for question in questions {
+            if options.copy {
+                let copy_id = copy_retargeted(conn, &cache, question.id, source, target, options)?;
+                println!("question {} copied to question {}", question.id, copy_id);
+                continue;
+            }
+            let query = restore_query(&cache, target, &source_dep, snapshot_query(&cache, &question.dataset_query)?)?;
+            let settings = restore_settings(&cache, target, snapshot_settings(&cache, &question.visualization_settings)?)?;
+            diesel::update(report_card::table.find(question.id))
+                .set((
+                    report_card::dataset_query.eq(query),
+                    report_card::visualization_settings.eq(settings),
+                    report_card::database_id.eq(target.db_id),
+                    report_card::table_id.eq(target.id),
+                    // The cached result metadata describes the old table's columns
+                    report_card::result_metadata.eq(None::<String>),
+                    report_card::updated_at.eq(timestamp),
+                ))
+                .execute(conn)?;
+            // Dashboard filters mapped onto the question also refer to the old table's fields
+            let cards = report_dashboardcard::table
+                .filter(report_dashboardcard::card_id.eq(question.id))
+                .get_results::<DashboardCard>(conn)?;
+            for card in cards {
+                let parameters = restore_parameters(&cache, question.id, target, snapshot_paremeters(&cache, &card.parameter_mappings)?)?;
+                let settings = restore_settings(&cache, target, snapshot_settings(&cache, &card.visualization_settings)?)?;
+                diesel::update(report_dashboardcard::table.find(card.id))
+                    .set((
+                        report_dashboardcard::parameter_mappings.eq(parameters),
+                        report_dashboardcard::visualization_settings.eq(settings),
+                        report_dashboardcard::updated_at.eq(timestamp),
+                    ))
+                    .execute(conn)?;
+            }
+            println!("question {} retargeted", question.id);
+        }
+        Ok(())
+    })?;
+    Ok(())
+}
+
+/// Copies a question onto another table, by snapshotting it and restoring the snapshot with the source table's
+/// references renamed to the target table (so the copy keeps its labels, result metadata, pinning and embedding).
+fn copy_retargeted(conn: &PgConnection, cache: &Cache, question_id: i32, source: &DbTable, target: &DbTable, options: &RetargetOptions) -> Result<i32, Box<dyn Error>> {
+    let path = options.into_collection.clone()
+        .ok_or(crate::error!("copies need another collection than the originals (use --into-collection)"))?;
+    let mut snapshot = snapshot_question(conn, cache, question_id)?;
+    snapshot.query = rename_snapshot_table(&snapshot.query, source, target)?;
+    snapshot.result_metadata = snapshot.result_metadata
+        .map(|x| rename_snapshot_table(&x, source, target))
+        .transpose()?;
+    snapshot.table = Some(TableDependency { table: target.name.clone(), schema: target.schema.clone() });
+    snapshot.database = Some(DatabaseDependency { database: cache.database(target.db_id)?.name.clone() });
+    let import_options = ImportOptions {
+        into_collection: Some(path),
+        create_collections: options.create_collections,
+        with_embedding: true,
+        creator: options.creator.clone(),
+        ..ImportOptions::default()
+    };
+    restore_question(conn, cache, snapshot, &import_options)
+}
+
+/// Replaces the references to a table (`snapshot:table:<schema>.<table>`, and its fields) in a snapshot with another table.
+fn rename_snapshot_table(snapshot: &SerializedSnapshot, source: &DbTable, target: &DbTable) -> Result<SerializedSnapshot, Box<dyn Error>> {
+    let source_name = format!("snapshot:table:{}.{}", source.schema_or("public"), source.name);
+    let target_name = format!("snapshot:table:{}.{}", target.schema_or("public"), target.name);
+    let table_re = Regex::new(&format!(r#""{}(:field:[^"]*)?""#, regex::escape(&source_name))).unwrap();
+    let text = snapshot.to_string();
+    let renamed = table_re.replace_all(&text, |caps: &Captures| {
+        format!(r#""{}{}""#, target_name, caps.get(1).map(|x| x.as_str()).unwrap_or_default())
+    });
+    SerializedSnapshot::new(&renamed)
+}
+
+/// Finds a question by its id, or by its name and collection path (e.g. "Finance/Revenue").
+pub fn find_question_id<S: ExportSource>(source: &S, cache: &Cache, identifier: &str) -> Result<i32, Box<dyn Error>> {
+    if let Ok(id) = identifier.parse() {
+        return Ok(id);
+    }
+    let path = parse_collection_path(identifier);
+    let name = path.last().ok_or(crate::error!("question name is empty"))?;
+    let candidates = source.questions_named(name)?;
+    find_item_id(cache, "question", &path, candidates)
+}
+
+/// Finds a dashboard by its id, or by its name and collection path (e.g. "Finance/Monthly KPIs").
+pub fn find_dashboard_id<S: ExportSource>(source: &S, cache: &Cache, identifier: &str) -> Result<i32, Box<dyn Error>> {
+    if let Ok(id) = identifier.parse() {
+        return Ok(id);
+    }
+    let path = parse_collection_path(identifier);
+    let name = path.last().ok_or(crate::error!("dashboard name is empty"))?;
+    let candidates = source.dashboards_named(name)?;
+    find_item_id(cache, "dashboard", &path, candidates)
+}
+
+/// Picks the item whose collection path matches, falling back to any collection when only a name is given.
+pub fn find_item_id(cache: &Cache, kind: &str, path: &[String], candidates: Candidates) -> Result<i32, Box<dyn Error>> {
+    let mut paths = Vec::new();
+    for (id, collection_id) in candidates {
+        let mut item_path = match collection_id {
+            Some(collection_id) => cache.collection_path(cache.collection(collection_id)?)?,
+            None => Vec::new(),
+        };
+        item_path.extend(path.last().cloned());
+        paths.push((id, item_path));
+    }
+    let exact = paths.iter().filter(|(_, x)| x.as_slice() == path).collect::<Vec<_>>();
+    let matches = if exact.is_empty() && path.len() == 1 { paths.iter().collect() } else { exact };
+    match matches.len() {
+        0 => Err(crate::error!("{} doesn't exist ({}: {})", kind, kind, path.join("/"))),
+        1 => Ok(matches[0].0),
+        _ => {
+            let names = matches.iter().map(|(id, x)| format!("{} (id: {})", x.join("/"), id)).collect::<Vec<_>>();
+            Err(crate::error!("{} is ambiguous ({}: {}, candidates: {})", kind, kind, path.join("/"), names.join(", ")))
+        }
+    }
+}
+
+/// Finds a table given as `<schema>.<table>` (or just `<table>` for the public schema).
+pub fn find_table<'a>(cache: &'a Cache, name: &str, database: Option<&str>) -> Result<&'a DbTable, Box<dyn Error>> {
+    let (schema, table) = match name.find('.') {
+        Some(index) => (&name[..index], &name[index + 1..]),
+        None => ("public", name),
+    };
+    let databases = database.map(|db| cache.search_databases(db));
+    let tables = cache.search_tables(table, schema)
+        .into_iter()
+        .filter(|x| databases.as_ref().map(|dbs| dbs.iter().any(|db| db.id == x.db_id)).unwrap_or(true))
+        .collect::<Vec<_>>();
+    match tables.len() {
+        0 => Err(crate::error!("table doesn't exist (tablename: {}.{})", schema, table)),
+        1 => Ok(tables[0]),
+        _ => Err(crate::error!("table exists in more than one database, pass the database (tablename: {}.{})", schema, table)),
+    }
+}
+
+/// Finds (or creates) the collection that an imported dashboard/question is placed in.
+fn import_collection(conn: &PgConnection, cache: &Cache, dep: &Option<CollectionDependency>, options: &ImportOptions) -> Result<Option<i32>, Box<dyn Error>> {
+    let path = match (&options.into_collection, dep) {
+        (Some(path), _) => path.clone(),
+        (None, Some(dep)) if options.create_collections => dep.path(),
+        (None, Some(dep)) => return Ok(Some(cache.find_collection(&dep.path())?.id)),
+        (None, None) => return Ok(None),
+    };
+    resolve_collection_path(conn, &path, options.create_collections)
+}
+
+/// Walks a collection path down from the root collection, optionally creating the missing collections.
+pub fn resolve_collection_path(conn: &PgConnection, path: &[String], create: bool) -> Result<Option<i32>, Box<dyn Error>> {
+    let mut collection_id = None;
+    let mut location = "/".to_string();
+    let mut color = DEFAULT_COLLECTION_COLOR.to_string();
+    for name in path {
+        let mut existing = collection::table
+            .filter(collection::name.eq(name))
+            .filter(collection::location.eq(&location))
+            .filter(collection::archived.eq(false))
+            .get_results::<Collection>(conn)?;
+        if existing.len() > 1 {
+            let ids = existing.iter().map(|x| x.id.to_string()).collect::<Vec<_>>();
+            return Err(crate::error!("collection is ambiguous (collection: {}, candidates: {})", path.join("/"), ids.join(", ")));
         }
+        let collection = match existing.pop() {
+            Some(collection) => collection,
+            None if create => {
+                diesel::insert_into(collection::table)
+                    .values((
+                        collection::name.eq(name),
+                        collection::color.eq(&color),
+                        collection::archived.eq(false),
+                        collection::location.eq(&location),
+                        collection::slug.eq(slugify(name)),
+                    ))
+                    .get_result::<Collection>(conn)?
+            }
+            None => return Err(crate::error!("collection doesn't exist (collection: {})", path.join("/"))),
+        };
+        location = format!("{}{}/", collection.location, collection.id);
+        color = collection.color.clone();
+        collection_id = Some(collection.id);
+    }
+    Ok(collection_id)
+}
+
+fn collection_dependency(cache: &Cache, collection: &Collection) -> Result<CollectionDependency, Box<dyn Error>> {
+    let mut ancestors = cache.collection_path(collection)?;
+    let collection = ancestors.pop().expect("Bug! Collection path is empty.");
+    Ok(CollectionDependency { collection, ancestors })
+}
+
+/// Converts a name to a slug the same way metabase does (e.g. "Monthly KPIs" -> "monthly_kpis").
+fn slugify(name: &str) -> String {
+    let re = Regex::new(r"[^A-Za-z0-9]+").unwrap();
+    re.replace_all(&name.to_lowercase(), "_").to_string()
+}
+
+pub fn find_database<'a>(cache: &'a Cache, name: &str) -> Result<&'a Database, Box<dyn Error>> {
+    let databases = cache.search_databases(name);
+    match databases.len() {
+        0 => match name.parse::<i32>() {
+            Ok(id) => cache.database(id),
+            Err(_) => Err(crate::error!("database doesn't exist (database: {})", name)),
+        },
+        1 => Ok(databases[0]),
+        _ => Err(crate::error!("database name is ambiguous (database: {})", name)),
+    }
+}
+
+/// Finds the table a question depends on (after mapping its names), within its database when the snapshot names one.
+pub fn discover_table<'a>(cache: &'a Cache, mappings: &NameMap, tbl: &TableDependency, db: &Option<DatabaseDependency>) -> Result<&'a DbTable, Box<dyn Error>> {
+    let schema = mappings.schema(tbl.schema_or("public"));
+    let tables = cache.search_tables(&tbl.table, schema);
+    if let Some(db) = db {
+        // A database that doesn't exist (e.g. a mistyped mapping) mustn't fall back to a table in another database
+        let name = mappings.database(&db.database);
+        let database = cache.search_databases(name)
+            .into_iter()
+            .next()
+            .ok_or(crate::error!("database doesn't exist (database: {})", name))?;
+        return tables.into_iter()
+            .find(|x| x.db_id == database.id)
+            .ok_or_else(|| crate::error!("table doesn't exist (tablename: {}.{}, database: {})", schema, tbl.table, name).into());
     }
     tables.iter().find(|x| x.active && x.visibility_type.is_none())
         .or_else(|| tables.iter().find(|x| x.active))
         .or_else(|| tables.iter().next())
         .map(|&x| x)
+        .ok_or_else(|| crate::error!("table doesn't exist (tablename: {}.{})", schema, tbl.table).into())
 }
 
-// FIXME: Use `regex::escape` on names
-fn restore_query(cache: &Cache, table: &DbTable, snapshot: SerializedSnapshot) -> Result<String, Box<dyn Error>> {
+// FIXME: Use `regex::escape` on field names
+pub fn restore_query(cache: &Cache, table: &DbTable, table_dep: &TableDependency, snapshot: SerializedSnapshot) -> Result<String, Box<dyn Error>> {
     let mut restored = snapshot.to_string();
     let database_re = Regex::new(r#""snapshot:database""#).unwrap();
     restored = database_re.replace_all(&restored, |_: &Captures| table.db_id.to_string()).to_string();
-    let table_re = Regex::new(&format!(r#""snapshot:table:{}.{}""#, table.schema_or("public"), table.name)).unwrap();
+    // The snapshot refers to the table by the names it was exported with, which may have been remapped
+    let table_name = format!("{}.{}", table_dep.schema_or("public"), table_dep.table);
+    let table_re = Regex::new(&format!(r#""snapshot:table:{}""#, regex::escape(&table_name))).unwrap();
     restored = table_re.replace_all(&restored, |_: &Captures| table.id.to_string()).to_string();
     let field_re = Regex::new(r#""snapshot:field:([A-Za-z_]+)""#).unwrap();
     let field_names = field_re.captures_iter(&restored).map(|x| x[1].to_string()).collect::<Vec<_>>();
@@ -271,10 +1192,10 @@ fn restore_query(cache: &Cache, table: &DbTable, snapshot: SerializedSnapshot) -
     Ok(restored)
 }
 
-fn restore_parameters(cache: &Cache, question: &Question, table: &DbTable, snapshot: SerializedSnapshot) -> Result<String, Box<dyn Error>> {
+pub fn restore_parameters(cache: &Cache, question_id: i32, table: &DbTable, snapshot: SerializedSnapshot) -> Result<String, Box<dyn Error>> {
     let mut restored = snapshot.to_string();
     let question_re = Regex::new(r#""snapshot:question""#).unwrap();
-    restored = question_re.replace_all(&restored, |_: &Captures| question.id.to_string()).to_string();
+    restored = question_re.replace_all(&restored, |_: &Captures| question_id.to_string()).to_string();
     let field_re = Regex::new(r#""snapshot:field:([A-Za-z_]+)""#).unwrap();
     let field_names = field_re.captures_iter(&restored).map(|x| x[1].to_string()).collect::<Vec<_>>();
     for field_name in field_names {
@@ -285,7 +1206,7 @@ fn restore_parameters(cache: &Cache, question: &Question, table: &DbTable, snaps
     Ok(restored)
 }
 
-fn restore_settings(cache: &Cache, table: &DbTable, snapshot: SerializedSnapshot) -> Result<String, Box<dyn Error>> {
+pub fn restore_settings(cache: &Cache, table: &DbTable, snapshot: SerializedSnapshot) -> Result<String, Box<dyn Error>> {
     let mut restored = snapshot.to_string();
     let field_re = Regex::new(r#""snapshot:field:([A-Za-z_]+)""#).unwrap();
     let field_names = field_re.captures_iter(&restored).map(|x| x[1].to_string()).collect::<Vec<_>>();
@@ -325,6 +1246,107 @@ fn snapshot_query(cache: &Cache, raw: &str) -> Result<SerializedSnapshot, Box<dy
     SerializedSnapshot::new(&snapshot)
 }
 
+/// Replaces the table and field ids of the columns in a question's result metadata with names (the columns can come
+/// from other tables).
+///
+/// Metadata that references missing tables or fields is left out (with a warning), as metabase recomputes it when the
+/// question runs.
+fn snapshot_result_metadata(cache: &Cache, question: &str, raw: &str) -> Result<Option<SerializedSnapshot>, Box<dyn Error>> {
+    let mut metadata: serde_json::Value = serde_json::from_str(raw)?;
+    let table_name = |table: &DbTable| format!("snapshot:table:{}.{}", table.schema_or("public"), table.name);
+    let mapped = map_metadata_ids(&mut metadata, &mut |kind, id| {
+        let id = match id.as_i64() {
+            Some(id) => id as i32,
+            None => return Ok(id.clone()),
+        };
+        let name = if kind == "table" {
+            table_name(cache.table(id)?)
+        } else {
+            let field = cache.field(id)?;
+            format!("{}:field:{}", table_name(cache.table(field.table_id)?), field.name)
+        };
+        Ok(serde_json::Value::String(name))
+    });
+    if let Err(err) = mapped {
+        eprintln!("warning: result metadata left out, metabase recomputes it (question: {}, {})", question, err);
+        return Ok(None);
+    }
+    Ok(Some(SerializedSnapshot::from_value(metadata)))
+}
+
+/// Resolves the table and field names in a question's result metadata, within the question's database.
+///
+/// Like when exporting, metadata that references missing tables or fields is left out (with a warning).
+pub fn restore_result_metadata(cache: &Cache, mappings: &NameMap, table: &DbTable, question: &str, snapshot: SerializedSnapshot) -> Result<Option<String>, Box<dyn Error>> {
+    let mut metadata: serde_json::Value = serde_json::from_str(&snapshot.to_string())?;
+    let mapped = map_metadata_ids(&mut metadata, &mut |_, name| {
+        let name = match name.as_str().and_then(|x| x.strip_prefix("snapshot:table:")) {
+            Some(name) => name,
+            None => return Ok(name.clone()),
+        };
+        let (table_name, field_name) = match name.split_once(":field:") {
+            Some((table_name, field_name)) => (table_name, Some(field_name)),
+            None => (name, None),
+        };
+        let (schema, table_name) = table_name.split_once('.').unwrap_or(("public", table_name));
+        let schema = mappings.schema(schema);
+        let metadata_table = cache.search_tables(table_name, schema)
+            .into_iter()
+            .find(|x| x.db_id == table.db_id)
+            .ok_or(crate::error!("table doesn't exist (tablename: {}.{})", schema, table_name))?;
+        let id = match field_name {
+            Some(field_name) => cache.table_field(metadata_table.id, field_name)?.id,
+            None => metadata_table.id,
+        };
+        Ok(id.into())
+    });
+    if let Err(err) = mapped {
+        eprintln!("warning: result metadata left out, metabase recomputes it (question: {}, {})", question, err);
+        return Ok(None);
+    }
+    Ok(Some(metadata.to_string()))
+}
+
+/// Maps the ids of each column in result metadata: the table id (`table_id`) and the field ids (`id`,
+/// `fk_target_field_id`, and the field references in `field_ref`).
+fn map_metadata_ids<F>(metadata: &mut serde_json::Value, map: &mut F) -> Result<(), Box<dyn Error>>
+where
+    F: FnMut(&str, &serde_json::Value) -> Result<serde_json::Value, Box<dyn Error>>,
+{
+    let columns = match metadata.as_array_mut() {
+        Some(columns) => columns,
+        None => return Ok(()),
+    };
+    for column in columns.iter_mut().filter_map(|x| x.as_object_mut()) {
+        for (key, value) in column.iter_mut() {
+            match key.as_str() {
+                "id" | "fk_target_field_id" if !value.is_null() => *value = map("field", value)?,
+                "table_id" if !value.is_null() => *value = map("table", value)?,
+                "field_ref" => map_field_refs(value, map)?,
+                _ => (),
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Maps the field ids of the `["field-id", <id>]` clauses in a field reference (e.g. `["fk->", ["field-id", 1], ["field-id", 2]]`).
+fn map_field_refs<F>(value: &mut serde_json::Value, map: &mut F) -> Result<(), Box<dyn Error>>
+where
+    F: FnMut(&str, &serde_json::Value) -> Result<serde_json::Value, Box<dyn Error>>,
+{
+    if let Some(items) = value.as_array_mut() {
+        if items.len() == 2 && items[0] == "field-id" {
+            items[1] = map("field", &items[1])?;
+            return Ok(());
+        }
+        for item in items {
+            map_field_refs(item, map)?;
+        }
+    }
+    Ok(())
+}
+
 fn snapshot_paremeters(cache: &Cache, raw: &str) -> Result<SerializedSnapshot, Box<dyn Error>> {
     let mut snapshot = raw.to_string();
     let question_re = Regex::new(r#""card_id":(\d+)"#).unwrap();
@@ -339,6 +1361,12 @@ fn snapshot_paremeters(cache: &Cache, raw: &str) -> Result<SerializedSnapshot, B
     SerializedSnapshot::new(&snapshot)
 }
 
+fn snapshot_details(database: &Database, raw: &str) -> Result<SerializedSnapshot, Box<dyn Error>> {
+    let mut details: serde_json::Value = serde_json::from_str(raw)?;
+    secrets::redact(&mut details, &["database", &database.name]);
+    Ok(SerializedSnapshot::from_value(details))
+}
+
 fn snapshot_settings(cache: &Cache, raw: &str) -> Result<SerializedSnapshot, Box<dyn Error>> {
     let mut snapshot = raw.to_string();
     let field_re = Regex::new(r#"\["field-id",(\d+)\]"#).unwrap();
@@ -432,3 +1460,126 @@ fn snapshot_order_by(conn: &PgConnection, raw: &serde_json::Value) -> Result<Vec
     Ok(fields)
 }
 */
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_collection_path_splits_names() {
+        assert_eq!(parse_collection_path("Finance/ Reports /"), vec!["Finance", "Reports"]);
+        assert!(parse_collection_path("/").is_empty());
+    }
+
+    #[test]
+    fn discover_table_stays_in_the_mapped_database() {
+        let cache = Cache::new(
+            Vec::new(),
+            vec![fixtures::database(1, "analytics"), fixtures::database(2, "analytics_staging")],
+            vec![fixtures::table(1, 1, "public", "orders"), fixtures::table(2, 2, "public", "orders")],
+            Vec::new(),
+        );
+        let orders = TableDependency { table: "orders".to_string(), schema: Some("public".to_string()) };
+        let database = |name: &str| Some(DatabaseDependency { database: name.to_string() });
+        let mut mappings = NameMap::default();
+        assert_eq!(discover_table(&cache, &mappings, &orders, &database("analytics_staging")).unwrap().id, 2);
+        mappings.database.insert("analytics_staging".to_string(), "analytics".to_string());
+        assert_eq!(discover_table(&cache, &mappings, &orders, &database("analytics_staging")).unwrap().id, 1);
+        mappings.database.insert("analytics_staging".to_string(), "analytcs".to_string());
+        let err = discover_table(&cache, &mappings, &orders, &database("analytics_staging")).unwrap_err();
+        assert!(err.to_string().contains("database doesn't exist (database: analytcs)"));
+        assert!(discover_table(&cache, &NameMap::default(), &orders, &None).is_ok());
+    }
+
+    #[test]
+    fn rename_snapshot_table_only_renames_the_source_table() {
+        let orders = fixtures::table(1, 1, "public", "orders");
+        let orders_v2 = fixtures::table(3, 1, "public", "orders_v2");
+        let snapshot = SerializedSnapshot::new(r#"[
+            {"table_id": "snapshot:table:public.orders", "id": "snapshot:table:public.orders:field:total"},
+            {"table_id": "snapshot:table:public.orders_archive", "fk_target_field_id": "snapshot:table:public.users:field:id"}
+        ]"#).unwrap();
+        let renamed = rename_snapshot_table(&snapshot, &orders, &orders_v2).unwrap();
+        assert_eq!(renamed.to_string(), SerializedSnapshot::new(r#"[
+            {"table_id": "snapshot:table:public.orders_v2", "id": "snapshot:table:public.orders_v2:field:total"},
+            {"table_id": "snapshot:table:public.orders_archive", "fk_target_field_id": "snapshot:table:public.users:field:id"}
+        ]"#).unwrap().to_string());
+    }
+
+    #[test]
+    fn duplicate_names_lists_every_question_sharing_a_name() {
+        let questions = vec![(1, "Revenue".to_string()), (2, "Emails".to_string()), (5, "Revenue".to_string())];
+        assert_eq!(duplicate_names(&questions), vec!["Revenue (id: 1)", "Revenue (id: 5)"]);
+        assert!(duplicate_names(&questions[..2]).is_empty());
+    }
+
+    #[test]
+    fn redact_setting_keeps_non_secrets_in_plain_text() {
+        assert_eq!(redact_setting("email-smtp-password", "hunter2".to_string()), "${MB_EMAIL_SMTP_PASSWORD}");
+        assert_eq!(redact_setting("enable-password-login", "true".to_string()), "true");
+        assert_eq!(redact_setting("password-complexity", "strong".to_string()), "strong");
+        assert_eq!(redact_setting("ldap-password", String::new()), "");
+    }
+
+    #[test]
+    fn restore_setting_only_substitutes_secrets() {
+        let env = |name: &str| if name == "MB_EMAIL_SMTP_PASSWORD" { Some("hunter2".to_string()) } else { None };
+        assert_eq!(restore_setting("email-smtp-password", "${MB_EMAIL_SMTP_PASSWORD}".to_string(), &env).unwrap(), "hunter2");
+        assert_eq!(restore_setting("site-name", "${NOT_A_VARIABLE}".to_string(), &env).unwrap(), "${NOT_A_VARIABLE}");
+    }
+
+    #[test]
+    fn public_uuid_parses_modes() {
+        assert!(PublicUuid::default() == PublicUuid::Drop);
+        assert!("preserve".parse::<PublicUuid>().unwrap() == PublicUuid::Preserve);
+        assert!("generate".parse::<PublicUuid>().unwrap() == PublicUuid::Generate);
+        assert!("keep".parse::<PublicUuid>().is_err());
+    }
+
+    #[test]
+    fn restore_embedding_checks_parameters() {
+        let embedding = |params: &str| Some(EmbeddingSnapshot { enabled: true, params: Some(SerializedSnapshot::new(params).unwrap()) });
+        let slugs = vec!["region".to_string()];
+        let options = ImportOptions { with_embedding: true, ..ImportOptions::default() };
+        let (enabled, params) = restore_embedding(embedding(r#"{"region":"locked"}"#), &slugs, &options).unwrap();
+        assert!(enabled);
+        assert_eq!(params.unwrap(), r#"{"region":"locked"}"#);
+        assert!(restore_embedding(embedding(r#"{"country":"locked"}"#), &slugs, &options).is_err());
+        assert!(restore_embedding(embedding(r#"{"region":"hidden"}"#), &slugs, &options).is_err());
+        // Embedding is only imported with `--with-embedding`
+        let (enabled, params) = restore_embedding(embedding(r#"{"region":"locked"}"#), &slugs, &ImportOptions::default()).unwrap();
+        assert!(!enabled && params.is_none());
+    }
+
+    #[test]
+    fn result_metadata_maps_column_ids() {
+        let cache = crate::model::fixtures::cache();
+        let raw = r#"[{"id":2,"table_id":1,"fk_target_field_id":4,"field_ref":["fk->",["field-id",3],["field-id",5]],"fingerprint":{"id":99}}]"#;
+        let snapshot = snapshot_result_metadata(&cache, "Revenue", raw).unwrap().unwrap();
+        let metadata: serde_json::Value = serde_json::from_str(&snapshot.to_string()).unwrap();
+        assert_eq!(metadata[0]["id"], "snapshot:table:public.orders:field:total");
+        assert_eq!(metadata[0]["table_id"], "snapshot:table:public.orders");
+        assert_eq!(metadata[0]["fk_target_field_id"], "snapshot:table:public.users:field:id");
+        assert_eq!(metadata[0]["field_ref"][2][1], "snapshot:table:public.users:field:email");
+        // Only the column's own ids are mapped
+        assert_eq!(metadata[0]["fingerprint"]["id"], 99);
+        let table = cache.table(1).unwrap();
+        let restored = restore_result_metadata(&cache, &NameMap::default(), table, "Revenue", snapshot).unwrap().unwrap();
+        assert_eq!(serde_json::from_str::<serde_json::Value>(&restored).unwrap(), serde_json::from_str::<serde_json::Value>(raw).unwrap());
+    }
+
+    #[test]
+    fn result_metadata_left_out_for_missing_fields() {
+        let cache = crate::model::fixtures::cache();
+        assert!(snapshot_result_metadata(&cache, "Revenue", r#"[{"id":42,"table_id":1}]"#).unwrap().is_none());
+        let snapshot = SerializedSnapshot::new(r#"[{"id":"snapshot:table:public.orders:field:deleted"}]"#).unwrap();
+        let table = cache.table(1).unwrap();
+        assert!(restore_result_metadata(&cache, &NameMap::default(), table, "Revenue", snapshot).unwrap().is_none());
+    }
+
+    #[test]
+    fn slugify_lowercases_and_joins_words() {
+        assert_eq!(slugify("Finance & Ops"), "finance_ops");
+        assert_eq!(slugify("Q3-2021"), "q3_2021");
+    }
+}
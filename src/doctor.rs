@@ -0,0 +1,324 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::error::Error;
+
+use diesel::prelude::*;
+use diesel::pg::PgConnection;
+use diesel::sql_types::{BigInt, Nullable, Text, Timestamp};
+use regex::Regex;
+
+use crate::model::USED_COLUMNS;
+
+/// The `table!` definitions, for the nullability of the columns the cli uses (`model::USED_COLUMNS`) and for reporting
+/// the tables it doesn't use.
+const SCHEMA: &str = include_str!("schema.rs");
+
+#[derive(QueryableByName)]
+struct TableRow {
+    #[sql_type = "Text"]
+    table_name: String,
+}
+
+#[derive(QueryableByName)]
+struct ColumnRow {
+    #[sql_type = "Text"]
+    table_name: String,
+    #[sql_type = "Text"]
+    column_name: String,
+    #[sql_type = "Text"]
+    is_nullable: String,
+}
+
+#[derive(QueryableByName)]
+struct MigrationRow {
+    #[sql_type = "Text"]
+    id: String,
+    #[sql_type = "Nullable<Timestamp>"]
+    applied_at: Option<chrono::NaiveDateTime>,
+    #[sql_type = "BigInt"]
+    count: i64,
+}
+
+/// The version of metabase that created an application database, from its migrations.
+struct Version {
+    /// The metabase version (e.g. `0.41`), when the migration ids include it
+    version: Option<String>,
+
+    /// The latest liquibase migration (from `databasechangelog`)
+    migration: Option<MigrationRow>,
+
+    /// The latest data migration (from `data_migrations`)
+    data_migration: Option<MigrationRow>,
+}
+
+struct Compatibility {
+    /// Tables and columns the cli uses that don't exist (which make its queries fail)
+    errors: Vec<String>,
+
+    /// Differences the cli can work with, but that may fail on some rows
+    warnings: Vec<String>,
+}
+
+/// Reports the metabase version of the application database, and whether the cli supports its schema.
+pub fn doctor(conn: &PgConnection) -> Result<(), Box<dyn Error>> {
+    let version = detect_version(conn)?;
+    println!("metabase version: {}", version.version.as_deref().unwrap_or("unknown"));
+    match &version.migration {
+        Some(migration) => println!("migrations: {} (latest: {}{})", migration.count, migration.id, applied_at(migration)),
+        None => println!("migrations: none (no databasechangelog table)"),
+    }
+    match &version.data_migration {
+        Some(migration) => println!("data migrations: {} (latest: {}{})", migration.count, migration.id, applied_at(migration)),
+        None => println!("data migrations: none"),
+    }
+
+    let compatibility = check_schema(conn)?;
+    for warning in &compatibility.warnings {
+        println!("warning: {}", warning);
+    }
+    for error in &compatibility.errors {
+        println!("error: {}", error);
+    }
+    if !compatibility.errors.is_empty() {
+        return Err(crate::error!("the schema isn't supported ({} problems)", compatibility.errors.len()));
+    }
+    eprintln!("the schema is supported");
+    Ok(())
+}
+
+/// Refuses mysql/mariadb database urls (other urls, e.g. libpq's `host=... dbname=...`, are left to postgres).
+///
+/// The cli's queries rely on postgres' `RETURNING` and `ON CONFLICT`, and `schema.rs` uses postgres types (e.g.
+/// `Timestamptz`), so instances with a mysql/mariadb database are exported and imported through the api instead.
+pub fn ensure_postgres(database_url: &str) -> Result<(), Box<dyn Error>> {
+    if is_mysql(database_url) {
+        return Err(crate::error!("mysql/mariadb databases can't be read directly; pass --api-url to export and import through metabase's api"));
+    }
+    Ok(())
+}
+
+pub fn is_mysql(database_url: &str) -> bool {
+    let scheme = database_url.split_once("://").map(|(scheme, _)| scheme.to_lowercase());
+    matches!(scheme.as_deref(), Some("mysql") | Some("mariadb"))
+}
+
+/// Refuses to run against an application database whose schema lacks tables or columns the cli uses.
+///
+/// Extra tables and columns (e.g. from a newer metabase) are fine, as the cli only selects the columns it knows.
+pub fn ensure_compatible(conn: &PgConnection) -> Result<(), Box<dyn Error>> {
+    let compatibility = check_schema(conn)?;
+    if let Some(error) = compatibility.errors.first() {
+        let version = detect_version(conn)?.version.unwrap_or_else(|| "unknown".to_string());
+        let more = match compatibility.errors.len() {
+            1 => String::new(),
+            n => format!(" and {} more problems", n - 1),
+        };
+        return Err(crate::error!("the metabase database isn't supported (version: {}, {}{}); run `doctor` for details", version, error, more));
+    }
+    Ok(())
+}
+
+fn detect_version(conn: &PgConnection) -> Result<Version, Box<dyn Error>> {
+    let tables = diesel::sql_query("select table_name::text as table_name from information_schema.tables where table_schema = current_schema()")
+        .load::<TableRow>(conn)?
+        .into_iter()
+        .map(|x| x.table_name)
+        .collect::<BTreeSet<_>>();
+    let latest = |query: &str| -> Result<Option<MigrationRow>, Box<dyn Error>> {
+        Ok(diesel::sql_query(query).load::<MigrationRow>(conn)?.into_iter().next())
+    };
+    let migration = if tables.contains("databasechangelog") {
+        latest("select id::text, dateexecuted as applied_at, count(*) over () as count from databasechangelog order by orderexecuted desc limit 1")?
+    } else {
+        None
+    };
+    let data_migration = if tables.contains("data_migrations") {
+        latest("select id::text, timestamp as applied_at, count(*) over () as count from data_migrations order by timestamp desc, id desc limit 1")?
+    } else {
+        None
+    };
+    let version = migration.as_ref().map(|x| parse_version(&x.id));
+    Ok(Version { version, migration, data_migration })
+}
+
+/// The metabase version of a liquibase migration id.
+fn parse_version(migration_id: &str) -> String {
+    // Since 0.38, migration ids are prefixed with the major version (e.g. `v41.00-004`); older ids are plain numbers
+    let re = Regex::new(r"^v(\d+)\.").unwrap();
+    match re.captures(migration_id) {
+        Some(captures) => format!("0.{}", &captures[1]),
+        None => "0.37 or older".to_string(),
+    }
+}
+
+fn check_schema(conn: &PgConnection) -> Result<Compatibility, Box<dyn Error>> {
+    let mut columns: BTreeMap<String, BTreeMap<String, bool>> = BTreeMap::new();
+    let rows = diesel::sql_query("select table_name::text as table_name, column_name::text as column_name, is_nullable::text as is_nullable from information_schema.columns where table_schema = current_schema()")
+        .load::<ColumnRow>(conn)?;
+    for row in rows {
+        columns.entry(row.table_name).or_default().insert(row.column_name, row.is_nullable == "YES");
+    }
+    Ok(check_columns(&columns))
+}
+
+/// Compares the columns of the application database (and whether they're nullable) with the ones the cli uses.
+///
+/// Missing tables and columns the cli uses are errors; the rest of `schema.rs` is only reported as warnings.
+fn check_columns(columns: &BTreeMap<String, BTreeMap<String, bool>>) -> Compatibility {
+    let schema = schema_columns();
+    let mut compatibility = Compatibility { errors: Vec::new(), warnings: Vec::new() };
+    for (table, used) in USED_COLUMNS {
+        let actual = match columns.get(*table) {
+            Some(actual) => actual,
+            None => {
+                compatibility.errors.push(format!("missing table `{}`", table));
+                continue;
+            }
+        };
+        for column in used.iter() {
+            let nullable = schema.get(*table).and_then(|x| x.get(*column)).copied().unwrap_or(true);
+            match actual.get(*column) {
+                None => compatibility.errors.push(format!("missing column `{}.{}`", table, column)),
+                Some(true) if !nullable => compatibility.warnings.push(format!("column `{}.{}` is nullable (null values can't be read)", table, column)),
+                Some(_) => (),
+            }
+        }
+    }
+    for (table, expected) in &schema {
+        let used = USED_COLUMNS.iter().find(|(used, _)| used == table).map(|(_, columns)| *columns);
+        match (columns.get(table), used) {
+            (None, None) => compatibility.warnings.push(format!("missing table `{}` (unused by the cli)", table)),
+            (None, Some(_)) => (),
+            (Some(actual), used) => {
+                let unused = expected.keys().filter(|x| !used.unwrap_or_default().contains(&x.as_str()));
+                for column in unused.filter(|x| !actual.contains_key(*x)) {
+                    compatibility.warnings.push(format!("missing column `{}.{}` (unused by the cli)", table, column));
+                }
+            }
+        }
+    }
+    compatibility
+}
+
+/// Parses the columns (and whether they're nullable) of each table in `schema.rs`.
+fn schema_columns() -> BTreeMap<String, BTreeMap<String, bool>> {
+    let table_re = Regex::new(r"^\s*(\w+) \(.*\) \{$").unwrap();
+    let sql_name_re = Regex::new(r#"^\s*#\[sql_name = "(\w+)"\]$"#).unwrap();
+    let column_re = Regex::new(r"^\s*(\w+) -> (.+),$").unwrap();
+    let mut tables: BTreeMap<String, BTreeMap<String, bool>> = BTreeMap::new();
+    let mut table = None;
+    let mut sql_name = None;
+    for line in SCHEMA.lines() {
+        if let Some(captures) = table_re.captures(line) {
+            table = Some(captures[1].to_string());
+        } else if let Some(captures) = sql_name_re.captures(line) {
+            sql_name = Some(captures[1].to_string());
+        } else if let (Some(table), Some(captures)) = (&table, column_re.captures(line)) {
+            let column = sql_name.take().unwrap_or_else(|| captures[1].to_string());
+            tables.entry(table.clone()).or_default().insert(column, captures[2].starts_with("Nullable<"));
+        } else if line.trim() == "}" {
+            table = None;
+        }
+    }
+    tables
+}
+
+fn applied_at(migration: &MigrationRow) -> String {
+    migration.applied_at.map(|x| format!(", applied {}", x.format("%Y-%m-%d"))).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn schema_columns_parses_table_definitions() {
+        let schema = schema_columns();
+        assert!(schema["dimension"].contains_key("type"));
+        assert!(!schema["dimension"].contains_key("type_"));
+        assert!(schema["report_card"]["table_id"]);
+        assert!(!schema["report_card"]["name"]);
+        for (table, columns) in USED_COLUMNS {
+            for column in columns.iter() {
+                assert!(schema.get(*table).and_then(|x| x.get(*column)).is_some(), "{}.{} isn't in schema.rs", table, column);
+            }
+        }
+    }
+
+    /// The modules with diesel queries, whose `<table>::<column>` references must all be in `USED_COLUMNS`.
+    const SOURCES: &[(&str, &str)] = &[
+        ("api.rs", include_str!("api.rs")),
+        ("check.rs", include_str!("check.rs")),
+        ("commands.rs", include_str!("commands.rs")),
+        ("impact.rs", include_str!("impact.rs")),
+        ("inventory.rs", include_str!("inventory.rs")),
+        ("lifecycle.rs", include_str!("lifecycle.rs")),
+        ("model.rs", include_str!("model.rs")),
+        ("permissions.rs", include_str!("permissions.rs")),
+        ("rewrite.rs", include_str!("rewrite.rs")),
+        ("stale.rs", include_str!("stale.rs")),
+        ("usage.rs", include_str!("usage.rs")),
+        ("users.rs", include_str!("users.rs")),
+    ];
+
+    #[test]
+    fn used_columns_include_every_queried_column() {
+        let schema = schema_columns();
+        let used = |table: &str, column: &str| USED_COLUMNS.iter().any(|(x, columns)| *x == table && columns.contains(&column));
+        let reference = Regex::new(r"\b(\w+)::(\w+)\b").unwrap();
+        for (file, source) in SOURCES {
+            for captures in reference.captures_iter(source) {
+                let (table, column) = (&captures[1], &captures[2]);
+                let columns = match schema.get(table) {
+                    Some(columns) => columns,
+                    None => continue,
+                };
+                if column == "table" || column == "all_columns" {
+                    assert!(USED_COLUMNS.iter().any(|(x, _)| *x == table), "{} queries `{}`, which isn't in USED_COLUMNS", file, table);
+                    continue;
+                }
+                // Columns named like rust keywords (e.g. `type_`) are renamed with `#[sql_name]`
+                let column = if columns.contains_key(column) { column } else { column.trim_end_matches('_') };
+                if columns.contains_key(column) {
+                    assert!(used(table, column), "{} queries `{}.{}`, which isn't in USED_COLUMNS", file, table, column);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn ensure_postgres_only_refuses_mysql() {
+        assert!(ensure_postgres("postgres://metabase@localhost/metabase").is_ok());
+        assert!(ensure_postgres("host=localhost dbname=metabase user=metabase").is_ok());
+        assert!(ensure_postgres("mysql://metabase@localhost/metabase").unwrap_err().to_string().contains("--api-url"));
+        assert!(ensure_postgres("MariaDB://metabase@localhost/metabase").is_err());
+    }
+
+    #[test]
+    fn parse_version_reads_migration_ids() {
+        assert_eq!(parse_version("v41.00-004"), "0.41");
+        assert_eq!(parse_version("165"), "0.37 or older");
+    }
+
+    #[test]
+    fn check_columns_only_fails_on_used_columns() {
+        let mut columns = schema_columns();
+        let compatibility = check_columns(&columns);
+        assert!(compatibility.errors.is_empty() && compatibility.warnings.is_empty());
+
+        columns.remove("qrtz_locks");
+        columns.get_mut("segment").unwrap().remove("description");
+        columns.get_mut("report_card").unwrap().insert("name".to_string(), true);
+        let compatibility = check_columns(&columns);
+        assert!(compatibility.errors.is_empty());
+        assert_eq!(compatibility.warnings, vec![
+            "column `report_card.name` is nullable (null values can't be read)",
+            "missing table `qrtz_locks` (unused by the cli)",
+            "missing column `segment.description` (unused by the cli)",
+        ]);
+
+        columns.remove("collection_revision");
+        columns.get_mut("pulse").unwrap().remove("collection_position");
+        let compatibility = check_columns(&columns);
+        assert_eq!(compatibility.errors, vec!["missing table `collection_revision`", "missing column `pulse.collection_position`"]);
+    }
+}
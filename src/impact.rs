@@ -0,0 +1,140 @@
+use std::collections::BTreeSet;
+use std::error::Error;
+
+use diesel::prelude::*;
+use diesel::pg::PgConnection;
+
+use crate::model::*;
+use crate::references;
+use crate::schema::*;
+
+/// Lists everything that references a field (given as `<database>.<schema>.<table>.<column>`).
+pub fn impact(conn: &PgConnection, column: &str) -> Result<(), Box<dyn Error>> {
+    let cache = Cache::load(conn)?;
+    let field = find_field(&cache, column)?;
+    let uses = |raw: &str| references::field_ids(raw).contains(&field.id);
+
+    let mut affected_questions = BTreeSet::new();
+    for question in report_card::table.order(report_card::id).get_results::<Question>(conn)? {
+        let mut places = Vec::new();
+        if uses(&question.dataset_query) {
+            places.push("query");
+        }
+        if uses(&question.visualization_settings) {
+            places.push("visualization settings");
+        }
+        if let Some(metadata) = &question.result_metadata {
+            if uses(metadata) {
+                places.push("result metadata");
+            }
+        }
+        if !places.is_empty() {
+            affected_questions.insert(question.id);
+            report("question", question.id, &question.name, question.archived, &places);
+        }
+    }
+    for dashboard in report_dashboard::table.order(report_dashboard::id).get_results::<Dashboard>(conn)? {
+        let mut places = Vec::new();
+        if uses(&dashboard.parameters) {
+            places.push("parameters".to_string());
+        }
+        let cards = report_dashboardcard::table
+            .filter(report_dashboardcard::dashboard_id.eq(dashboard.id))
+            .order(report_dashboardcard::id)
+            .get_results::<DashboardCard>(conn)?;
+        for card in cards {
+            if uses(&card.parameter_mappings) {
+                places.push(format!("card {} parameter mappings", card.id));
+            }
+            if uses(&card.visualization_settings) {
+                places.push(format!("card {} visualization settings", card.id));
+            }
+        }
+        if !places.is_empty() {
+            let places = places.iter().map(|x| x.as_str()).collect::<Vec<_>>();
+            report("dashboard", dashboard.id, &dashboard.name, dashboard.archived, &places);
+        }
+    }
+    for segment in segment::table.order(segment::id).select(Segment::COLUMNS).get_results::<Segment>(conn)? {
+        if uses(&segment.definition) {
+            report("segment", segment.id, &segment.name, segment.archived, &["definition"]);
+        }
+    }
+    for metric in metric::table.order(metric::id).select(Metric::COLUMNS).get_results::<Metric>(conn)? {
+        let important = diesel::select(
+            diesel::dsl::exists(
+                metric_important_field::table
+                    .filter(metric_important_field::metric_id.eq(metric.id))
+                    .filter(metric_important_field::field_id.eq(field.id))
+            )
+        )
+        .get_result::<bool>(conn)?;
+        let mut places = Vec::new();
+        if uses(&metric.definition) {
+            places.push("definition");
+        }
+        if important {
+            places.push("important fields");
+        }
+        if !places.is_empty() {
+            report("metric", metric.id, &metric.name, metric.archived, &places);
+        }
+    }
+    let dimensions = dimension::table
+        .select((dimension::id, dimension::name, dimension::field_id))
+        .filter(dimension::field_id.eq(field.id).or(dimension::human_readable_field_id.eq(field.id)))
+        .order(dimension::id)
+        .get_results::<(i32, String, i32)>(conn)?;
+    for (id, name, field_id) in dimensions {
+        let place = if field_id == field.id { "field" } else { "human readable field" };
+        report("dimension", id, &name, false, &[place]);
+    }
+    let pulse_cards = pulse_card::table
+        .select((pulse_card::pulse_id, pulse_card::card_id))
+        .order(pulse_card::pulse_id)
+        .get_results::<(i32, i32)>(conn)?;
+    for (pulse_id, card_id) in pulse_cards {
+        if affected_questions.contains(&card_id) {
+            let pulse = pulse::table.find(pulse_id).select(Pulse::COLUMNS).get_result::<Pulse>(conn)?;
+            let place = format!("question {}", card_id);
+            report(pulse.kind(), pulse.id, pulse.name.as_deref().unwrap_or(""), pulse.archived.unwrap_or(false), &[&place]);
+        }
+    }
+    Ok(())
+}
+
+fn report(kind: &str, id: i32, name: &str, archived: bool, places: &[&str]) {
+    let archived = if archived { ", archived" } else { "" };
+    println!("{} {} ({}{}): {}", kind, id, name, archived, places.join(", "));
+}
+
+fn find_field<'a>(cache: &'a Cache, column: &str) -> Result<&'a DbField, Box<dyn Error>> {
+    // Split from the right, as only the database name may reasonably contain a '.'
+    let parts = column.rsplitn(4, '.').collect::<Vec<_>>();
+    if parts.len() != 4 {
+        return Err(crate::error!("expected a column in the form `<database>.<schema>.<table>.<column>` (got: {})", column));
+    }
+    let (field, table, schema, database) = (parts[0], parts[1], parts[2], parts[3]);
+    let databases = cache.search_databases(database);
+    let table = cache.search_tables(table, schema)
+        .into_iter()
+        .find(|x| databases.iter().any(|db| db.id == x.db_id))
+        .ok_or(crate::error!("table doesn't exist (tablename: {}.{}.{})", database, schema, table))?;
+    cache.table_field(table.id, field)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::fixtures;
+
+    #[test]
+    fn find_field_resolves_qualified_columns() {
+        let cache = fixtures::cache();
+        assert_eq!(find_field(&cache, "analytics.public.orders.total").unwrap().id, 2);
+        assert_eq!(find_field(&cache, "analytics.public.users.id").unwrap().id, 4);
+        assert!(find_field(&cache, "public.orders.total").is_err());
+        assert!(find_field(&cache, "analytics.public.orders.missing").is_err());
+        assert!(find_field(&cache, "other.public.orders.total").is_err());
+    }
+}
@@ -0,0 +1,283 @@
+use std::collections::BTreeMap;
+use std::error::Error;
+
+use diesel::prelude::*;
+use diesel::pg::PgConnection;
+
+use crate::model::*;
+use crate::schema::*;
+
+#[derive(Default)]
+pub struct ListOptions {
+    /// Only list items of this type (one of: collection, question, dashboard, pulse or alert)
+    pub kind: Option<String>,
+
+    /// Only list the contents of the collection at this path
+    pub collection: Option<Vec<String>>,
+
+    /// Only list items created by the user with this email
+    pub creator: Option<String>,
+
+    /// Only list questions with this label (by slug or name)
+    pub label: Option<String>,
+
+    /// Whether archived collections and items are included
+    pub archived: bool,
+
+    /// Whether the inventory is written as json (rather than as an indented tree)
+    pub json: bool,
+}
+
+#[derive(Serialize)]
+struct CollectionNode {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    id: Option<i32>,
+    name: String,
+    archived: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    owner: Option<String>,
+    items: Vec<ItemNode>,
+    collections: Vec<CollectionNode>,
+}
+
+#[derive(Clone, Serialize)]
+struct ItemNode {
+    #[serde(rename = "type")]
+    kind: &'static str,
+    id: i32,
+    name: String,
+    archived: bool,
+    creator: Option<String>,
+}
+
+struct Inventory {
+    cache: Cache,
+    users: BTreeMap<i32, User>,
+    items: BTreeMap<Option<i32>, Vec<ItemNode>>,
+}
+
+pub fn list(conn: &PgConnection, options: &ListOptions) -> Result<(), Box<dyn Error>> {
+    check_options(options)?;
+    let inventory = Inventory::load(conn, options)?;
+    let root = match &options.collection {
+        Some(path) => {
+            let collection = inventory.cache.find_collection(path)?;
+            inventory.collection_node(Some(collection), options)
+        }
+        None => inventory.collection_node(None, options),
+    };
+    if options.json {
+        println!("{}", serde_json::to_string(&root)?);
+    } else {
+        print_tree(&root, 0);
+    }
+    Ok(())
+}
+
+impl Inventory {
+    fn load(conn: &PgConnection, options: &ListOptions) -> Result<Inventory, Box<dyn Error>> {
+        let cache = Cache::load(conn)?;
+        let users = core_user::table
+            .select(User::COLUMNS)
+            .get_results::<User>(conn)?
+            .into_iter()
+            .map(|row| (row.id, row))
+            .collect::<BTreeMap<_, _>>();
+        let creator_id = match &options.creator {
+            Some(email) => {
+                let user = users.values()
+                    .find(|x| x.email.eq_ignore_ascii_case(email))
+                    .ok_or(crate::error!("user doesn't exist (email: {})", email))?;
+                Some(user.id)
+            }
+            None => None,
+        };
+        let labelled = match &options.label {
+            Some(name) => {
+                let label = label::table
+                    .filter(label::slug.eq(name).or(label::name.eq(name)))
+                    .get_result::<Label>(conn)
+                    .optional()?
+                    .ok_or(crate::error!("label doesn't exist (label: {})", name))?;
+                let cards = card_label::table
+                    .select(card_label::card_id)
+                    .filter(card_label::label_id.eq(label.id))
+                    .get_results::<i32>(conn)?;
+                Some(cards)
+            }
+            None => None,
+        };
+        // Only questions have labels, so the other items are left out when filtering by label
+        let listed = |kind: &str, creator: i32, archived: bool| {
+            (labelled.is_none() || kind == "question")
+                && options.kind.as_ref().map(|x| x == kind).unwrap_or(true)
+                && creator_id.map(|x| x == creator).unwrap_or(true)
+                && (options.archived || !archived)
+        };
+
+        let mut items: BTreeMap<Option<i32>, Vec<ItemNode>> = BTreeMap::new();
+        let email = |id: i32| users.get(&id).map(|x| x.email.clone());
+        for question in report_card::table.order(report_card::name).get_results::<Question>(conn)? {
+            let has_label = labelled.as_ref().map(|x| x.contains(&question.id)).unwrap_or(true);
+            if has_label && listed("question", question.creator_id, question.archived) {
+                items.entry(question.collection_id).or_default().push(ItemNode {
+                    kind: "question",
+                    id: question.id,
+                    name: question.name,
+                    archived: question.archived,
+                    creator: email(question.creator_id),
+                });
+            }
+        }
+        for dashboard in report_dashboard::table.order(report_dashboard::name).get_results::<Dashboard>(conn)? {
+            if listed("dashboard", dashboard.creator_id, dashboard.archived) {
+                items.entry(dashboard.collection_id).or_default().push(ItemNode {
+                    kind: "dashboard",
+                    id: dashboard.id,
+                    name: dashboard.name,
+                    archived: dashboard.archived,
+                    creator: email(dashboard.creator_id),
+                });
+            }
+        }
+        for pulse in pulse::table.order(pulse::name).select(Pulse::COLUMNS).get_results::<Pulse>(conn)? {
+            let archived = pulse.archived.unwrap_or(false);
+            if listed(pulse.kind(), pulse.creator_id, archived) {
+                items.entry(pulse.collection_id).or_default().push(ItemNode {
+                    kind: pulse.kind(),
+                    id: pulse.id,
+                    name: pulse.name.unwrap_or_default(),
+                    archived,
+                    creator: email(pulse.creator_id),
+                });
+            }
+        }
+        Ok(Inventory { cache, users, items })
+    }
+
+    fn collection_node(&self, collection: Option<&Collection>, options: &ListOptions) -> CollectionNode {
+        let location = match collection {
+            Some(collection) => format!("{}{}/", collection.location, collection.id),
+            None => "/".to_string(),
+        };
+        let mut children = self.cache.collections()
+            .filter(|x| x.location == location && (options.archived || !x.archived))
+            .collect::<Vec<_>>();
+        children.sort_by(|a, b| a.name.cmp(&b.name));
+        let collections = children.into_iter()
+            .map(|child| self.collection_node(Some(child), options))
+            // When filtering items, collections without any matching items are omitted
+            .filter(|node| !self.filtered(options) || !node.items.is_empty() || !node.collections.is_empty())
+            .collect();
+        let items = match options.kind.as_deref() {
+            Some("collection") => Vec::new(),
+            _ => self.items(collection.map(|x| x.id)),
+        };
+        CollectionNode {
+            id: collection.map(|x| x.id),
+            name: collection.map(|x| x.name.clone()).unwrap_or_else(|| "Root collection".to_string()),
+            archived: collection.map(|x| x.archived).unwrap_or(false),
+            owner: collection
+                .and_then(|x| x.personal_owner_id)
+                .and_then(|id| self.users.get(&id))
+                .map(|x| x.email.clone()),
+            items,
+            collections,
+        }
+    }
+
+    fn filtered(&self, options: &ListOptions) -> bool {
+        options.creator.is_some() || options.label.is_some() || options.kind.as_ref().map(|x| x != "collection").unwrap_or(false)
+    }
+
+    fn items(&self, collection_id: Option<i32>) -> Vec<ItemNode> {
+        self.items.get(&collection_id).cloned().unwrap_or_default()
+    }
+}
+
+fn print_tree(node: &CollectionNode, depth: usize) {
+    let indent = "  ".repeat(depth);
+    let mut details = Vec::new();
+    if let Some(id) = node.id {
+        details.push(format!("id: {}", id));
+    }
+    if let Some(owner) = &node.owner {
+        details.push(format!("owner: {}", owner));
+    }
+    if node.archived {
+        details.push("archived".to_string());
+    }
+    if details.is_empty() {
+        println!("{}{}/", indent, node.name);
+    } else {
+        println!("{}{}/ ({})", indent, node.name, details.join(", "));
+    }
+    for item in &node.items {
+        let mut details = vec![format!("id: {}", item.id)];
+        if let Some(creator) = &item.creator {
+            details.push(format!("creator: {}", creator));
+        }
+        if item.archived {
+            details.push("archived".to_string());
+        }
+        println!("{}  [{}] {} ({})", indent, item.kind, item.name, details.join(", "));
+    }
+    for child in &node.collections {
+        print_tree(child, depth + 1);
+    }
+}
+
+fn check_options(options: &ListOptions) -> Result<(), Box<dyn Error>> {
+    match options.kind.as_deref() {
+        None | Some("collection") | Some("question") | Some("dashboard") | Some("pulse") | Some("alert") => (),
+        Some(kind) => return Err(crate::error!("unknown item type `{}`", kind)),
+    }
+    // Collections have no creator, so none of them would match
+    if options.kind.as_deref() == Some("collection") && options.creator.is_some() {
+        return Err(crate::error!("collections can't be filtered by creator"));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::fixtures::collection;
+
+    fn item(kind: &'static str, id: i32) -> ItemNode {
+        ItemNode { kind, id, name: format!("{} {}", kind, id), archived: false, creator: None }
+    }
+
+    fn inventory() -> Inventory {
+        let collections = vec![collection(1, "Finance", "/"), collection(2, "Reports", "/1/"), collection(3, "Empty", "/")];
+        let mut items = BTreeMap::new();
+        items.insert(None, vec![item("question", 1)]);
+        items.insert(Some(2), vec![item("dashboard", 2)]);
+        Inventory { cache: Cache::new(collections, Vec::new(), Vec::new(), Vec::new()), users: BTreeMap::new(), items }
+    }
+
+    #[test]
+    fn collection_node_builds_the_tree() {
+        let root = inventory().collection_node(None, &ListOptions::default());
+        assert_eq!(root.items.len(), 1);
+        let names = root.collections.iter().map(|x| x.name.as_str()).collect::<Vec<_>>();
+        assert_eq!(names, vec!["Empty", "Finance"]);
+        assert_eq!(root.collections[1].collections[0].items[0].id, 2);
+    }
+
+    #[test]
+    fn collection_node_omits_collections_without_matches() {
+        let options = ListOptions { kind: Some("dashboard".to_string()), ..ListOptions::default() };
+        let root = inventory().collection_node(None, &options);
+        let names = root.collections.iter().map(|x| x.name.as_str()).collect::<Vec<_>>();
+        assert_eq!(names, vec!["Finance"]);
+    }
+
+    #[test]
+    fn check_options_rejects_collections_by_creator() {
+        let options = ListOptions { kind: Some("collection".to_string()), creator: Some("a@example.com".to_string()), ..ListOptions::default() };
+        assert!(check_options(&options).unwrap_err().to_string().contains("can't be filtered by creator"));
+        let options = ListOptions { kind: Some("card".to_string()), ..ListOptions::default() };
+        assert!(check_options(&options).unwrap_err().to_string().contains("unknown item type"));
+    }
+}
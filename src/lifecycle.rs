@@ -0,0 +1,583 @@
+use std::collections::BTreeSet;
+use std::error::Error;
+use std::io::BufRead;
+
+use chrono::Utc;
+use diesel::prelude::*;
+use diesel::pg::PgConnection;
+
+use crate::commands::{find_item_id, parse_collection_path, resolve_collection_path};
+use crate::model::*;
+use crate::references;
+use crate::schema::*;
+
+pub struct LifecycleOptions {
+    /// The type of the items (one of: question, dashboard, collection or pulse)
+    pub kind: String,
+
+    /// The ids/paths of the items (e.g. '1' or 'Finance/Monthly KPIs')
+    pub identifiers: Vec<String>,
+
+    /// Whether to skip the confirmation prompt
+    pub yes: bool,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Kind {
+    Question,
+    Dashboard,
+    Collection,
+    Pulse,
+}
+
+impl Kind {
+    fn parse(kind: &str) -> Result<Kind, Box<dyn Error>> {
+        match kind {
+            "question" => Ok(Kind::Question),
+            "dashboard" => Ok(Kind::Dashboard),
+            "collection" => Ok(Kind::Collection),
+            "pulse" => Ok(Kind::Pulse),
+            _ => Err(crate::error!("unknown item type `{}`", kind)),
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Kind::Question => "question",
+            Kind::Dashboard => "dashboard",
+            Kind::Collection => "collection",
+            Kind::Pulse => "pulse",
+        }
+    }
+}
+
+struct Target {
+    kind: Kind,
+    id: i32,
+    /// The item's name, prefixed by its collection path
+    path: String,
+}
+
+impl Target {
+    fn describe(&self) -> String {
+        format!("{} {} ({})", self.kind.name(), self.id, self.path)
+    }
+}
+
+/// Archives (or unarchives) questions, dashboards, collections or pulses.
+///
+/// Archiving a collection archives its descendant collections and the questions, dashboards and
+/// pulses in them as well, as metabase does (and unarchiving it unarchives them all again).
+pub fn archive(conn: &PgConnection, options: &LifecycleOptions, archived: bool) -> Result<(), Box<dyn Error>> {
+    let cache = Cache::load(conn)?;
+    let targets = find_targets(conn, &cache, options, Some(!archived))?;
+    let action = if archived { "archive" } else { "unarchive" };
+    for target in &targets {
+        if target.kind == Kind::Collection {
+            let collection = cache.collection(target.id)?;
+            check_personal(collection)?;
+            if !archived && cache.collection_ancestors(collection)?.iter().any(|x| x.archived) {
+                return Err(crate::error!("the parent collection is archived, unarchive it first (collection: {})", target.path));
+            }
+        }
+    }
+    let plan = targets.iter()
+        .map(|x| match x.kind {
+            Kind::Collection => format!("{} {} and its contents", action, x.describe()),
+            _ => format!("{} {}", action, x.describe()),
+        })
+        .collect::<Vec<_>>();
+    confirm(&plan, options.yes)?;
+
+    let timestamp = Utc::now();
+    conn.transaction::<_, Box<dyn Error>, _>(|| {
+        for target in &targets {
+            match target.kind {
+                Kind::Question => {
+                    diesel::update(report_card::table.find(target.id))
+                        .set((report_card::archived.eq(archived), report_card::updated_at.eq(timestamp)))
+                        .execute(conn)?;
+                }
+                Kind::Dashboard => {
+                    diesel::update(report_dashboard::table.find(target.id))
+                        .set((report_dashboard::archived.eq(archived), report_dashboard::updated_at.eq(timestamp)))
+                        .execute(conn)?;
+                }
+                Kind::Collection => {
+                    let ids = collection_tree(&cache, cache.collection(target.id)?).iter().map(|x| x.id).collect::<Vec<_>>();
+                    diesel::update(collection::table.filter(collection::id.eq_any(&ids)))
+                        .set(collection::archived.eq(archived))
+                        .execute(conn)?;
+                    diesel::update(report_card::table.filter(report_card::collection_id.eq_any(&ids)))
+                        .set((report_card::archived.eq(archived), report_card::updated_at.eq(timestamp)))
+                        .execute(conn)?;
+                    diesel::update(report_dashboard::table.filter(report_dashboard::collection_id.eq_any(&ids)))
+                        .set((report_dashboard::archived.eq(archived), report_dashboard::updated_at.eq(timestamp)))
+                        .execute(conn)?;
+                    diesel::update(pulse::table.filter(pulse::collection_id.eq_any(&ids)))
+                        .set((pulse::archived.eq(archived), pulse::updated_at.eq(timestamp)))
+                        .execute(conn)?;
+                }
+                Kind::Pulse => {
+                    diesel::update(pulse::table.find(target.id))
+                        .set((pulse::archived.eq(archived), pulse::updated_at.eq(timestamp)))
+                        .execute(conn)?;
+                }
+            }
+        }
+        Ok(())
+    })?;
+    eprintln!("{}d {} items", action, targets.len());
+    Ok(())
+}
+
+/// Moves questions, dashboards, collections or pulses into another collection (`[]` for the root collection).
+pub fn move_items(conn: &PgConnection, options: &LifecycleOptions, to_collection: &[String], create_collections: bool) -> Result<(), Box<dyn Error>> {
+    let cache = Cache::load(conn)?;
+    let targets = find_targets(conn, &cache, options, None)?;
+    for target in targets.iter().filter(|x| x.kind == Kind::Collection) {
+        check_personal(cache.collection(target.id)?)?;
+    }
+    let destination = if to_collection.is_empty() { "/".to_string() } else { to_collection.join("/") };
+    let plan = targets.iter().map(|x| format!("move {} to {}", x.describe(), destination)).collect::<Vec<_>>();
+    confirm(&plan, options.yes)?;
+
+    let timestamp = Utc::now();
+    conn.transaction::<_, Box<dyn Error>, _>(|| {
+        let collection_id = resolve_collection_path(conn, to_collection, create_collections)?;
+        for target in &targets {
+            match target.kind {
+                Kind::Question => {
+                    diesel::update(report_card::table.find(target.id))
+                        .set((
+                            report_card::collection_id.eq(collection_id),
+                            report_card::collection_position.eq(None::<i16>),
+                            report_card::updated_at.eq(timestamp),
+                        ))
+                        .execute(conn)?;
+                }
+                Kind::Dashboard => {
+                    diesel::update(report_dashboard::table.find(target.id))
+                        .set((
+                            report_dashboard::collection_id.eq(collection_id),
+                            report_dashboard::collection_position.eq(None::<i16>),
+                            report_dashboard::updated_at.eq(timestamp),
+                        ))
+                        .execute(conn)?;
+                }
+                Kind::Collection => move_collection(conn, target, collection_id)?,
+                Kind::Pulse => {
+                    diesel::update(pulse::table.find(target.id))
+                        .set((
+                            pulse::collection_id.eq(collection_id),
+                            pulse::collection_position.eq(None::<i16>),
+                            pulse::updated_at.eq(timestamp),
+                        ))
+                        .execute(conn)?;
+                }
+            }
+        }
+        Ok(())
+    })?;
+    eprintln!("moved {} items to {}", targets.len(), destination);
+    Ok(())
+}
+
+/// Moves a collection (and with it, its descendants) by rewriting their `location`s.
+fn move_collection(conn: &PgConnection, target: &Target, parent_id: Option<i32>) -> Result<(), Box<dyn Error>> {
+    // Collections are re-read, as the destination may have been created (and locations changed) in this transaction
+    let collection = collection::table.find(target.id).get_result::<Collection>(conn)?;
+    let location = match parent_id {
+        Some(parent_id) => {
+            let parent = collection::table.find(parent_id).get_result::<Collection>(conn)?;
+            format!("{}{}/", parent.location, parent.id)
+        }
+        None => "/".to_string(),
+    };
+    let old_prefix = format!("{}{}/", collection.location, collection.id);
+    if parent_id == Some(collection.id) || location.starts_with(&old_prefix) {
+        return Err(crate::error!("a collection can't be moved into itself (collection: {})", target.path));
+    }
+    let new_prefix = format!("{}{}/", location, collection.id);
+    diesel::update(collection::table.find(collection.id))
+        .set(collection::location.eq(&location))
+        .execute(conn)?;
+    let descendants = collection::table
+        .filter(collection::location.like(format!("{}%", old_prefix)))
+        .get_results::<Collection>(conn)?;
+    for descendant in descendants {
+        let location = format!("{}{}", new_prefix, &descendant.location[old_prefix.len()..]);
+        diesel::update(collection::table.find(descendant.id))
+            .set(collection::location.eq(location))
+            .execute(conn)?;
+    }
+    Ok(())
+}
+
+/// The items removed by a delete, including the contents of deleted collections.
+#[derive(Default)]
+struct Deletion {
+    questions: BTreeSet<i32>,
+    dashboards: BTreeSet<i32>,
+    pulses: BTreeSet<i32>,
+    /// Ordered so that children are deleted before their parents
+    collections: Vec<i32>,
+}
+
+/// Deletes questions, dashboards, collections or pulses (along with their revisions, favorites and labels).
+///
+/// Without `cascade`, questions that are still used by a dashboard or pulse and collections that
+/// aren't empty are refused; with it, the dashboard cards, pulse cards and contents are removed too.
+pub fn delete(conn: &PgConnection, options: &LifecycleOptions, cascade: bool) -> Result<(), Box<dyn Error>> {
+    let cache = Cache::load(conn)?;
+    let targets = find_targets(conn, &cache, options, None)?;
+
+    let mut deletion = Deletion::default();
+    let mut plan = Vec::new();
+    for target in &targets {
+        match target.kind {
+            Kind::Question => {
+                deletion.questions.insert(target.id);
+            }
+            Kind::Dashboard => {
+                deletion.dashboards.insert(target.id);
+            }
+            Kind::Pulse => {
+                deletion.pulses.insert(target.id);
+            }
+            Kind::Collection => {
+                let collection = cache.collection(target.id)?;
+                check_personal(collection)?;
+                let mut collections = collection_tree(&cache, collection);
+                let ids = collections.iter().map(|x| x.id).collect::<Vec<_>>();
+                let questions = report_card::table
+                    .select(report_card::id)
+                    .filter(report_card::collection_id.eq_any(&ids))
+                    .get_results::<i32>(conn)?;
+                let dashboards = report_dashboard::table
+                    .select(report_dashboard::id)
+                    .filter(report_dashboard::collection_id.eq_any(&ids))
+                    .get_results::<i32>(conn)?;
+                let pulses = pulse::table
+                    .select(pulse::id)
+                    .filter(pulse::collection_id.eq_any(&ids))
+                    .get_results::<i32>(conn)?;
+                let contents = format!(
+                    "{} collections, {} questions, {} dashboards, {} pulses",
+                    collections.len() - 1, questions.len(), dashboards.len(), pulses.len(),
+                );
+                if collections.len() > 1 || !questions.is_empty() || !dashboards.is_empty() || !pulses.is_empty() {
+                    if !cascade {
+                        return Err(crate::error!("collection isn't empty, pass --cascade to delete its contents (collection: {}, contents: {})", target.path, contents));
+                    }
+                    plan.push(format!("delete {} and its contents ({})", target.describe(), contents));
+                } else {
+                    plan.push(format!("delete {}", target.describe()));
+                }
+                collections.sort_by_key(|x| std::cmp::Reverse(x.location.len()));
+                deletion.collections.extend(collections.into_iter().map(|x| x.id));
+                deletion.questions.extend(questions);
+                deletion.dashboards.extend(dashboards);
+                deletion.pulses.extend(pulses);
+                continue;
+            }
+        }
+        plan.push(format!("delete {}", target.describe()));
+    }
+
+    for question in report_card::table.filter(report_card::id.ne_all(deletion.questions.iter().cloned().collect::<Vec<_>>())).get_results::<Question>(conn)? {
+        if let Some(source) = references::question_ids(&question.dataset_query).into_iter().find(|x| deletion.questions.contains(x)) {
+            return Err(crate::error!("question {} is the source of question {} ({}), which isn't deleted", source, question.id, question.name));
+        }
+    }
+    let questions = deletion.questions.iter().cloned().collect::<Vec<_>>();
+    let dashboards = deletion.dashboards.iter().cloned().collect::<Vec<_>>();
+    let pulses = deletion.pulses.iter().cloned().collect::<Vec<_>>();
+    let dashboard_cards = report_dashboardcard::table
+        .select(report_dashboardcard::id)
+        .filter(report_dashboardcard::card_id.eq_any(&questions))
+        .filter(report_dashboardcard::dashboard_id.ne_all(&dashboards))
+        .get_results::<i32>(conn)?;
+    let series = dashboardcard_series::table
+        .inner_join(report_dashboardcard::table)
+        .select(dashboardcard_series::id)
+        .filter(dashboardcard_series::card_id.eq_any(&questions))
+        .filter(report_dashboardcard::dashboard_id.ne_all(&dashboards))
+        .get_results::<i32>(conn)?;
+    let pulse_cards = pulse_card::table
+        .select(pulse_card::id)
+        .filter(pulse_card::card_id.eq_any(&questions))
+        .filter(pulse_card::pulse_id.ne_all(&pulses))
+        .get_results::<i32>(conn)?;
+    if !dashboard_cards.is_empty() || !series.is_empty() || !pulse_cards.is_empty() {
+        let uses = format!("{} dashboard cards, {} dashboard card series, {} pulse cards", dashboard_cards.len(), series.len(), pulse_cards.len());
+        if !cascade {
+            return Err(crate::error!("questions are still used elsewhere, pass --cascade to remove them ({})", uses));
+        }
+        plan.push(format!("remove the questions from {}", uses));
+    }
+    confirm(&plan, options.yes)?;
+
+    conn.transaction::<_, Box<dyn Error>, _>(|| {
+        for &id in &deletion.dashboards {
+            delete_dashboard(conn, id)?;
+        }
+        for &id in &deletion.pulses {
+            delete_pulse(conn, id)?;
+        }
+        for &id in &deletion.questions {
+            delete_question(conn, id)?;
+        }
+        for &id in &deletion.collections {
+            delete_collection(conn, id)?;
+        }
+        Ok(())
+    })?;
+    eprintln!(
+        "deleted {} questions, {} dashboards, {} pulses and {} collections",
+        deletion.questions.len(), deletion.dashboards.len(), deletion.pulses.len(), deletion.collections.len(),
+    );
+    Ok(())
+}
+
+fn delete_question(conn: &PgConnection, id: i32) -> Result<(), Box<dyn Error>> {
+    let dashboard_cards = report_dashboardcard::table
+        .select(report_dashboardcard::id)
+        .filter(report_dashboardcard::card_id.eq(id))
+        .get_results::<i32>(conn)?;
+    diesel::delete(dashboardcard_series::table.filter(
+        dashboardcard_series::card_id.eq(id).or(dashboardcard_series::dashboardcard_id.eq_any(&dashboard_cards))
+    ))
+    .execute(conn)?;
+    diesel::delete(report_dashboardcard::table.filter(report_dashboardcard::card_id.eq(id))).execute(conn)?;
+
+    // Alerts only have a single card, so they are deleted along with it
+    let pulses = pulse_card::table
+        .select(pulse_card::pulse_id)
+        .filter(pulse_card::card_id.eq(id))
+        .get_results::<i32>(conn)?;
+    diesel::delete(pulse_card::table.filter(pulse_card::card_id.eq(id))).execute(conn)?;
+    for pulse in pulse::table.filter(pulse::id.eq_any(&pulses)).select(Pulse::COLUMNS).get_results::<Pulse>(conn)? {
+        let empty = !diesel::select(diesel::dsl::exists(pulse_card::table.filter(pulse_card::pulse_id.eq(pulse.id))))
+            .get_result::<bool>(conn)?;
+        if pulse.alert_condition.is_some() && empty {
+            delete_pulse(conn, pulse.id)?;
+        }
+    }
+
+    diesel::delete(card_label::table.filter(card_label::card_id.eq(id))).execute(conn)?;
+    diesel::delete(report_cardfavorite::table.filter(report_cardfavorite::card_id.eq(id))).execute(conn)?;
+    diesel::delete(revision::table.filter(revision::model.eq("Card")).filter(revision::model_id.eq(id))).execute(conn)?;
+    diesel::delete(report_card::table.find(id)).execute(conn)?;
+    Ok(())
+}
+
+fn delete_dashboard(conn: &PgConnection, id: i32) -> Result<(), Box<dyn Error>> {
+    let dashboard_cards = report_dashboardcard::table
+        .select(report_dashboardcard::id)
+        .filter(report_dashboardcard::dashboard_id.eq(id))
+        .get_results::<i32>(conn)?;
+    diesel::delete(dashboardcard_series::table.filter(dashboardcard_series::dashboardcard_id.eq_any(&dashboard_cards))).execute(conn)?;
+    diesel::delete(report_dashboardcard::table.filter(report_dashboardcard::dashboard_id.eq(id))).execute(conn)?;
+    diesel::delete(dashboard_favorite::table.filter(dashboard_favorite::dashboard_id.eq(id))).execute(conn)?;
+    diesel::delete(revision::table.filter(revision::model.eq("Dashboard")).filter(revision::model_id.eq(id))).execute(conn)?;
+    diesel::delete(report_dashboard::table.find(id)).execute(conn)?;
+    Ok(())
+}
+
+fn delete_pulse(conn: &PgConnection, id: i32) -> Result<(), Box<dyn Error>> {
+    let channels = pulse_channel::table
+        .select(pulse_channel::id)
+        .filter(pulse_channel::pulse_id.eq(id))
+        .get_results::<i32>(conn)?;
+    diesel::delete(pulse_channel_recipient::table.filter(pulse_channel_recipient::pulse_channel_id.eq_any(&channels))).execute(conn)?;
+    diesel::delete(pulse_channel::table.filter(pulse_channel::pulse_id.eq(id))).execute(conn)?;
+    diesel::delete(pulse_card::table.filter(pulse_card::pulse_id.eq(id))).execute(conn)?;
+    diesel::delete(pulse::table.find(id)).execute(conn)?;
+    Ok(())
+}
+
+fn delete_collection(conn: &PgConnection, id: i32) -> Result<(), Box<dyn Error>> {
+    let objects = vec![format!("/collection/{}/", id), format!("/collection/{}/read/", id)];
+    diesel::delete(permissions::table.filter(permissions::object.eq_any(objects))).execute(conn)?;
+    diesel::delete(collection::table.find(id)).execute(conn)?;
+    Ok(())
+}
+
+/// Returns a collection along with its descendants.
+fn collection_tree<'a>(cache: &'a Cache, collection: &Collection) -> Vec<&'a Collection> {
+    let prefix = format!("{}{}/", collection.location, collection.id);
+    cache.collections()
+        .filter(|x| x.id == collection.id || x.location.starts_with(&prefix))
+        .collect()
+}
+
+/// Personal collections belong to a user, so metabase doesn't allow them to be archived, moved or deleted.
+fn check_personal(collection: &Collection) -> Result<(), Box<dyn Error>> {
+    if collection.personal_owner_id.is_some() {
+        return Err(crate::error!("personal collections can't be changed (collection: {})", collection.id));
+    }
+    Ok(())
+}
+
+/// Prints what is about to change and asks for confirmation (unless `yes` is set).
+pub fn confirm(plan: &[String], yes: bool) -> Result<(), Box<dyn Error>> {
+    for line in plan {
+        eprintln!("{}", line);
+    }
+    if yes {
+        return Ok(());
+    }
+    eprint!("Continue? [y/N] ");
+    let mut answer = String::new();
+    if std::io::stdin().lock().read_line(&mut answer)? == 0 {
+        eprintln!();
+    }
+    match answer.trim() {
+        "y" | "Y" | "yes" => Ok(()),
+        _ => Err(crate::error!("aborted (pass --yes to skip the confirmation)")),
+    }
+}
+
+fn find_targets(conn: &PgConnection, cache: &Cache, options: &LifecycleOptions, archived: Option<bool>) -> Result<Vec<Target>, Box<dyn Error>> {
+    let kind = Kind::parse(&options.kind)?;
+    let mut targets = Vec::new();
+    for identifier in &options.identifiers {
+        let id = match identifier.parse() {
+            Ok(id) => id,
+            Err(_) => find_id(conn, cache, kind, identifier, archived)?,
+        };
+        let target = describe(conn, cache, kind, id)?;
+        if !targets.iter().any(|x: &Target| x.id == target.id) {
+            targets.push(target);
+        }
+    }
+    Ok(targets)
+}
+
+/// Finds an item by its name and collection path, only considering (un)archived items when `archived` is set.
+fn find_id(conn: &PgConnection, cache: &Cache, kind: Kind, identifier: &str, archived: Option<bool>) -> Result<i32, Box<dyn Error>> {
+    let path = parse_collection_path(identifier);
+    let name = path.last().ok_or(crate::error!("{} name is empty", kind.name()))?;
+    let candidates = match kind {
+        Kind::Question => {
+            let mut query = report_card::table
+                .select((report_card::id, report_card::collection_id))
+                .filter(report_card::name.eq(name))
+                .into_boxed();
+            if let Some(archived) = archived {
+                query = query.filter(report_card::archived.eq(archived));
+            }
+            query.get_results::<(i32, Option<i32>)>(conn)?
+        }
+        Kind::Dashboard => {
+            let mut query = report_dashboard::table
+                .select((report_dashboard::id, report_dashboard::collection_id))
+                .filter(report_dashboard::name.eq(name))
+                .into_boxed();
+            if let Some(archived) = archived {
+                query = query.filter(report_dashboard::archived.eq(archived));
+            }
+            query.get_results::<(i32, Option<i32>)>(conn)?
+        }
+        Kind::Collection => cache.collections()
+            .filter(|x| &x.name == name && archived.map(|archived| x.archived == archived).unwrap_or(true))
+            .map(|x| (x.id, x.location.split('/').rfind(|x| !x.is_empty()).and_then(|x| x.parse().ok())))
+            .collect(),
+        Kind::Pulse => {
+            let mut query = pulse::table
+                .select((pulse::id, pulse::collection_id))
+                .filter(pulse::name.eq(name))
+                .into_boxed();
+            match archived {
+                Some(true) => query = query.filter(pulse::archived.eq(true)),
+                Some(false) => query = query.filter(pulse::archived.eq(false).or(pulse::archived.is_null())),
+                None => (),
+            }
+            query.get_results::<(i32, Option<i32>)>(conn)?
+        }
+    };
+    find_item_id(cache, kind.name(), &path, candidates)
+}
+
+fn describe(conn: &PgConnection, cache: &Cache, kind: Kind, id: i32) -> Result<Target, Box<dyn Error>> {
+    let missing = || crate::error!("{} doesn't exist (id: {})", kind.name(), id);
+    let (name, collection_id) = match kind {
+        Kind::Question => report_card::table
+            .select((report_card::name, report_card::collection_id))
+            .find(id)
+            .get_result::<(String, Option<i32>)>(conn)
+            .optional()?
+            .ok_or_else(missing)?,
+        Kind::Dashboard => report_dashboard::table
+            .select((report_dashboard::name, report_dashboard::collection_id))
+            .find(id)
+            .get_result::<(String, Option<i32>)>(conn)
+            .optional()?
+            .ok_or_else(missing)?,
+        Kind::Collection => {
+            let collection = cache.collection(id).map_err(|_| missing())?;
+            let path = cache.collection_path(collection)?.join("/");
+            return Ok(Target { kind, id, path });
+        }
+        Kind::Pulse => {
+            let (name, collection_id) = pulse::table
+                .select((pulse::name, pulse::collection_id))
+                .find(id)
+                .get_result::<(Option<String>, Option<i32>)>(conn)
+                .optional()?
+                .ok_or_else(missing)?;
+            (name.unwrap_or_default(), collection_id)
+        }
+    };
+    let mut path = match collection_id {
+        Some(collection_id) => cache.collection_path(cache.collection(collection_id)?)?,
+        None => Vec::new(),
+    };
+    path.push(name);
+    Ok(Target { kind, id, path: path.join("/") })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::fixtures;
+
+    #[test]
+    fn kind_round_trips_names() {
+        for name in &["question", "dashboard", "collection", "pulse"] {
+            assert_eq!(Kind::parse(name).unwrap().name(), *name);
+        }
+        assert!(Kind::parse("card").is_err());
+    }
+
+    #[test]
+    fn check_personal_refuses_personal_collections() {
+        let mut collection = fixtures::collection(1, "Finance", "/");
+        assert!(check_personal(&collection).is_ok());
+        collection.personal_owner_id = Some(1);
+        assert!(check_personal(&collection).is_err());
+    }
+
+    #[test]
+    fn collection_tree_includes_descendants_only() {
+        let collections = vec![
+            fixtures::collection(1, "Finance", "/"),
+            fixtures::collection(2, "Reports", "/1/"),
+            fixtures::collection(3, "Monthly", "/1/2/"),
+            fixtures::collection(4, "Marketing", "/"),
+            fixtures::collection(11, "Other", "/"),
+            fixtures::collection(12, "Budget", "/11/"),
+        ];
+        let cache = Cache::new(collections, Vec::new(), Vec::new(), Vec::new());
+        let ids = |id| collection_tree(&cache, cache.collection(id).unwrap()).iter().map(|x| x.id).collect::<Vec<_>>();
+        assert_eq!(ids(1), vec![1, 2, 3]);
+        assert_eq!(ids(2), vec![2, 3]);
+        assert_eq!(ids(4), vec![4]);
+    }
+
+    #[test]
+    fn confirm_skips_the_prompt_with_yes() {
+        assert!(confirm(&["archive question 1 (Finance/Revenue)".to_string()], true).is_ok());
+    }
+}
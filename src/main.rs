@@ -1,11 +1,28 @@
 #[macro_use] extern crate diesel;
 #[macro_use] extern crate serde_derive;
 
+mod api;
+mod check;
 mod commands;
+mod config;
+mod doctor;
+mod impact;
+mod inventory;
+mod lifecycle;
+mod mapping;
 mod model;
+mod permissions;
+mod references;
+mod rewrite;
 mod schema;
+mod secrets;
 mod snapshot;
+mod stale;
+mod usage;
+mod users;
 
+use diesel::pg::PgConnection;
+use diesel::Connection;
 use structopt::StructOpt;
 
 #[macro_export]
@@ -17,55 +34,615 @@ macro_rules! error {
 
 #[derive(StructOpt)]
 struct Arguments {
+    #[structopt(long = "profile", help = "Use a profile from ~/.config/metabase-cli/config.toml (or set METABASE_PROFILE)")]
+    profile: Option<String>,
     #[structopt(long = "database-url", help = "The url to connect to metabase's database")]
     database_url: Option<String>,
+    #[structopt(long = "api-url", help = "Use metabase's api at this url for `import` and `export`, when there's no database url")]
+    api_url: Option<String>,
+    #[structopt(long = "api-key", help = "The api key used with --api-url (or set METABASE_API_KEY)")]
+    api_key: Option<String>,
+    #[structopt(long = "session", help = "The session id used with --api-url (or set METABASE_SESSION, or METABASE_USERNAME and METABASE_PASSWORD)")]
+    session: Option<String>,
+    #[structopt(long = "as-user", help = "The email of the user that imported items and permission changes are attributed to")]
+    as_user: Option<String>,
+    #[structopt(long = "skip-compatibility-check", help = "Run even if metabase's database isn't postgres or lacks tables or columns the cli uses")]
+    skip_compatibility_check: bool,
     #[structopt(subcommand)]
     command: Command,
 }
 
 #[derive(StructOpt)]
 enum Command {
+    #[structopt(name = "archive", about = "Archive questions, dashboards, collections or pulses")]
+    Archive {
+        #[structopt(help = "One of: question, dashboard, collection or pulse")]
+        kind: String,
+
+        #[structopt(help = "The ids/paths of the items (e.g. '1' or 'Finance/Monthly KPIs')", raw(required = "true"))]
+        identifiers: Vec<String>,
+
+        #[structopt(long = "yes", help = "Don't ask for confirmation")]
+        yes: bool,
+    },
+
+    #[structopt(name = "unarchive", about = "Unarchive questions, dashboards, collections or pulses")]
+    Unarchive {
+        #[structopt(help = "One of: question, dashboard, collection or pulse")]
+        kind: String,
+
+        #[structopt(help = "The ids/paths of the items (e.g. '1' or 'Finance/Monthly KPIs')", raw(required = "true"))]
+        identifiers: Vec<String>,
+
+        #[structopt(long = "yes", help = "Don't ask for confirmation")]
+        yes: bool,
+    },
+
+    #[structopt(name = "move", about = "Move questions, dashboards, collections or pulses into another collection")]
+    Move {
+        #[structopt(help = "One of: question, dashboard, collection or pulse")]
+        kind: String,
+
+        #[structopt(help = "The ids/paths of the items (e.g. '1' or 'Finance/Monthly KPIs')", raw(required = "true"))]
+        identifiers: Vec<String>,
+
+        #[structopt(long = "to-collection", help = "The collection path to move the items into (e.g. 'Finance/Reports', or '/' for the root collection)")]
+        to_collection: String,
+
+        #[structopt(long = "create-collections", help = "Create any missing collections in the collection path")]
+        create_collections: bool,
+
+        #[structopt(long = "yes", help = "Don't ask for confirmation")]
+        yes: bool,
+    },
+
+    #[structopt(name = "delete", about = "Delete questions, dashboards, collections or pulses")]
+    Delete {
+        #[structopt(help = "One of: question, dashboard, collection or pulse")]
+        kind: String,
+
+        #[structopt(help = "The ids/paths of the items (e.g. '1' or 'Finance/Monthly KPIs')", raw(required = "true"))]
+        identifiers: Vec<String>,
+
+        #[structopt(long = "cascade", help = "Also remove the items from dashboards and pulses, and delete the contents of collections")]
+        cascade: bool,
+
+        #[structopt(long = "yes", help = "Don't ask for confirmation")]
+        yes: bool,
+    },
+
+    #[structopt(name = "clone", about = "Copy a dashboard (and optionally its questions) within the same instance")]
+    Clone {
+        #[structopt(help = "Only 'dashboard' can be cloned")]
+        datasource: String,
+
+        #[structopt(help = "The id/path of the dashboard (e.g. '1' or 'Finance/Monthly KPIs')")]
+        identifier: String,
+
+        #[structopt(long = "to-collection", help = "Place the copy in this collection path (e.g. 'Customers/Acme', or '/' for the root collection)")]
+        to_collection: String,
+
+        #[structopt(long = "create-collections", help = "Create any missing collections in the collection path")]
+        create_collections: bool,
+
+        #[structopt(long = "name", help = "The name of the copy")]
+        name: Option<String>,
+
+        #[structopt(long = "deep", help = "Copy the dashboard's questions too, into the same collection as the copy")]
+        deep: bool,
+    },
+
     #[structopt(name = "export", about = "Export the config for a collection, question or dashboard to stdout")]
     Export {
         // #[structopt(help = "One of: database, table, collection, dashboard, or question")]
-        #[structopt(help = "One of: table or question")]
+        #[structopt(help = "One of: database, dashboard, question or settings")]
         datasource: String,
 
-        #[structopt(help = "The id/name to be exported (e.g. '1')")]
-        identifier: String,
+        #[structopt(help = "The id/name to be exported (e.g. '1', or 'Finance/Monthly KPIs' for a dashboard/question)")]
+        identifier: Option<String>,
+
+        #[structopt(long = "include", help = "Only export the settings whose keys match this pattern (e.g. 'email-*'); may be repeated")]
+        include: Vec<String>,
+
+        #[structopt(long = "exclude", help = "Don't export the settings whose keys match this pattern; may be repeated")]
+        exclude: Vec<String>,
+    },
+
+    #[structopt(name = "check", about = "Check every question, segment and metric for references to missing, inactive or hidden tables and fields")]
+    Check,
+
+    #[structopt(name = "doctor", about = "Report the version of metabase's database, and whether its schema is supported")]
+    Doctor,
+
+    #[structopt(name = "impact", about = "List every question, dashboard, segment, metric, dimension and pulse that references a column")]
+    Impact {
+        #[structopt(help = "The column, as <database>.<schema>.<table>.<column> (e.g. 'analytics.public.orders.total')")]
+        column: String,
+    },
+
+    #[structopt(name = "ls", about = "List the collections, questions, dashboards and pulses as a tree")]
+    List {
+        #[structopt(long = "type", help = "Only list items of this type (one of: collection, question, dashboard, pulse or alert)")]
+        kind: Option<String>,
+
+        #[structopt(long = "collection", help = "Only list the contents of this collection path (e.g. 'Finance/Reports')")]
+        collection: Option<String>,
+
+        #[structopt(long = "creator", help = "Only list items created by the user with this email")]
+        creator: Option<String>,
+
+        #[structopt(long = "label", help = "Only list questions with this label (by slug or name)")]
+        label: Option<String>,
+
+        #[structopt(long = "archived", help = "Include archived collections and items")]
+        archived: bool,
+
+        #[structopt(long = "json", help = "Write the inventory as json")]
+        json: bool,
+    },
+
+    #[structopt(name = "stale", about = "List the questions and dashboards that haven't been viewed in a number of days")]
+    Stale {
+        #[structopt(long = "days", default_value = "90", help = "The number of days without a view")]
+        days: i64,
+
+        #[structopt(long = "type", help = "Only list items of this type (one of: question or dashboard)")]
+        kind: Option<String>,
+
+        #[structopt(long = "ids", help = "Only write the ids, one per line (e.g. to pipe into `xargs metabase-cli archive question --yes`)")]
+        ids: bool,
+
+        #[structopt(long = "json", help = "Write the report as json")]
+        json: bool,
+    },
+
+    #[structopt(name = "usage", about = "Summarise the views and activity of dashboards, questions, users or collections")]
+    Usage {
+        #[structopt(long = "from", help = "The first day of the report, as YYYY-MM-DD (defaults to 30 days before --to)")]
+        from: Option<String>,
+
+        #[structopt(long = "to", help = "The day after the last day of the report, as YYYY-MM-DD (defaults to tomorrow)")]
+        to: Option<String>,
+
+        #[structopt(long = "by", default_value = "dashboard", help = "Group the views by one of: dashboard, question, user or collection")]
+        by: String,
+
+        #[structopt(long = "limit", default_value = "20", help = "The number of rows to report")]
+        limit: usize,
+
+        #[structopt(long = "format", default_value = "table", help = "One of: table, csv or json")]
+        format: String,
+    },
+
+    #[structopt(name = "retarget", about = "Point questions that use one table at another table (in place, or as copies)")]
+    Retarget {
+        #[structopt(long = "from-table", help = "The table the questions currently use, as <schema>.<table> (e.g. 'staging.orders')")]
+        from_table: String,
+
+        #[structopt(long = "from-database", help = "The database of the source table (when the table exists in more than one database)")]
+        from_database: Option<String>,
+
+        #[structopt(long = "to-table", help = "The table the questions should use, as <schema>.<table> (e.g. 'public.orders')")]
+        to_table: String,
+
+        #[structopt(long = "to-database", help = "The database of the target table (when the table exists in more than one database)")]
+        to_database: Option<String>,
+
+        #[structopt(long = "question", help = "Only retarget this question (by id or path); may be repeated")]
+        questions: Vec<String>,
+
+        #[structopt(long = "copy", help = "Create retargeted copies of the questions instead of updating them", raw(requires = "\"into_collection\""))]
+        copy: bool,
+
+        #[structopt(long = "into-collection", help = "Place the copies in this collection path (e.g. 'Finance/Reports', or '/' for the root collection)")]
+        into_collection: Option<String>,
+
+        #[structopt(long = "create-collections", help = "Create any missing collections in the collection path")]
+        create_collections: bool,
+    },
+
+    #[structopt(name = "rewrite-field", about = "Point every question and dashboard card that references a field at another field of the same table")]
+    RewriteField {
+        #[structopt(long = "table", help = "The table, as <schema>.<table> (e.g. 'public.orders')")]
+        table: String,
+
+        #[structopt(long = "database", help = "The database of the table (when the table exists in more than one database)")]
+        database: Option<String>,
+
+        #[structopt(long = "from", help = "The name of the field that is currently referenced")]
+        from: String,
+
+        #[structopt(long = "to", help = "The name of the field that should be referenced instead")]
+        to: String,
+
+        #[structopt(long = "dry-run", help = "Print the changes without writing them")]
+        dry_run: bool,
+    },
+
+    #[structopt(name = "users", about = "List or deactivate users")]
+    Users {
+        #[structopt(subcommand)]
+        command: UsersCommand,
+    },
+
+    #[structopt(name = "groups", about = "List groups and manage their members")]
+    Groups {
+        #[structopt(subcommand)]
+        command: GroupsCommand,
+    },
+
+    #[structopt(name = "permissions", about = "Export, diff or apply the permissions of each group")]
+    Permissions {
+        #[structopt(subcommand)]
+        command: PermissionsCommand,
+    },
+
+    #[structopt(name = "collection-permissions", about = "Export, diff or apply the access of each group to each collection")]
+    CollectionPermissions {
+        #[structopt(subcommand)]
+        command: PermissionsCommand,
     },
 
     #[structopt(name = "import", about = "Import the records in a metabase config file")]
     Import {
         #[allow(dead_code)]
         filename: String,
+
+        #[structopt(long = "map", help = "Rename a dependency while importing (e.g. 'database:analytics_staging=analytics' or 'schema:staging=public')")]
+        map: Vec<String>,
+
+        #[structopt(long = "mappings", help = "A toml file of [database] and [schema] renames to apply while importing")]
+        mappings: Option<String>,
+
+        #[structopt(long = "into-collection", help = "Import into this collection path (e.g. 'Finance/Reports', or '/' for the root collection)")]
+        into_collection: Option<String>,
+
+        #[structopt(long = "create-collections", help = "Create any missing collections in the collection path")]
+        create_collections: bool,
+
+        #[structopt(long = "with-embedding", help = "Keep embedding enabled, with the embedded parameters' config (rather than disabling embedding)")]
+        with_embedding: bool,
+
+        #[structopt(long = "public-uuid", default_value = "drop", help = "What happens to public links (one of: drop, preserve or generate)")]
+        public_uuid: commands::PublicUuid,
+
+        #[structopt(long = "yes", help = "Don't ask for confirmation (when the profile is a production profile)")]
+        yes: bool,
+    },
+}
+
+#[derive(StructOpt)]
+enum UsersCommand {
+    #[structopt(name = "list", about = "List the users, with their groups")]
+    List {
+        #[structopt(long = "json", help = "Write the users as json")]
+        json: bool,
+    },
+
+    #[structopt(name = "deactivate", about = "Deactivate a user (and end their sessions)")]
+    Deactivate {
+        #[structopt(help = "The email of the user")]
+        email: String,
+    },
+}
+
+#[derive(StructOpt)]
+enum GroupsCommand {
+    #[structopt(name = "list", about = "List the groups")]
+    List {
+        #[structopt(long = "members", help = "List the members of each group")]
+        members: bool,
+
+        #[structopt(long = "json", help = "Write the groups (and their members) as json")]
+        json: bool,
+    },
+
+    #[structopt(name = "add-member", about = "Add a user to a group")]
+    AddMember {
+        #[structopt(help = "The name of the group")]
+        group: String,
+
+        #[structopt(help = "The email of the user")]
+        email: String,
+    },
+
+    #[structopt(name = "remove-member", about = "Remove a user from a group")]
+    RemoveMember {
+        #[structopt(help = "The name of the group")]
+        group: String,
+
+        #[structopt(help = "The email of the user")]
+        email: String,
+    },
+
+    #[structopt(name = "import", about = "Make the members of the groups in a yaml/json file match the file")]
+    Import {
+        #[structopt(help = "A yaml/json file with a `groups` map of group names to member emails")]
+        filename: String,
+
+        #[structopt(long = "dry-run", help = "Print the changes without writing them")]
+        dry_run: bool,
     },
 }
 
+#[derive(StructOpt)]
+enum PermissionsCommand {
+    #[structopt(name = "export", about = "Export the permissions to stdout (with names in place of ids)")]
+    Export,
+
+    #[structopt(name = "diff", about = "Print the permissions that applying a file would grant (+) and revoke (-)")]
+    Diff {
+        #[structopt(help = "A file written by `export`")]
+        filename: String,
+    },
+
+    #[structopt(name = "apply", about = "Make the permissions of the groups in a file match the file")]
+    Apply {
+        #[structopt(help = "A file written by `export`")]
+        filename: String,
+    },
+}
+
+impl Command {
+    /// Whether the command changes metabase (and so is refused by read-only profiles).
+    fn writes(&self) -> bool {
+        match self {
+            Command::Archive { .. }
+            | Command::Unarchive { .. }
+            | Command::Move { .. }
+            | Command::Delete { .. }
+            | Command::Clone { .. }
+            | Command::Retarget { .. }
+            | Command::Import { .. } => true,
+            Command::RewriteField { dry_run, .. } => !dry_run,
+            Command::Users { command } => match command {
+                UsersCommand::List { .. } => false,
+                UsersCommand::Deactivate { .. } => true,
+            },
+            Command::Groups { command } => match command {
+                GroupsCommand::List { .. } => false,
+                GroupsCommand::AddMember { .. } | GroupsCommand::RemoveMember { .. } => true,
+                GroupsCommand::Import { dry_run, .. } => !dry_run,
+            },
+            Command::Permissions { command } | Command::CollectionPermissions { command } => match command {
+                PermissionsCommand::Export | PermissionsCommand::Diff { .. } => false,
+                PermissionsCommand::Apply { .. } => true,
+            },
+            Command::Export { .. }
+            | Command::Check
+            | Command::Doctor
+            | Command::Impact { .. }
+            | Command::List { .. }
+            | Command::Stale { .. }
+            | Command::Usage { .. } => false,
+        }
+    }
+}
+
+fn import_options(base: mapping::NameMap, map: Vec<String>, mappings: Option<String>) -> Result<commands::ImportOptions, Box<dyn std::error::Error>> {
+    let mut options = commands::ImportOptions { mappings: base, ..commands::ImportOptions::default() };
+    if let Some(filename) = mappings {
+        options.mappings.extend(mapping::NameMap::load(&filename)?);
+    }
+    for rule in map {
+        options.mappings.add(&rule)?;
+    }
+    Ok(options)
+}
+
+/// Connects to metabase's database, refusing databases the cli doesn't support (unless the check is skipped).
+fn connect(database_url: &str, command: &Command, skip_compatibility_check: bool) -> Result<PgConnection, Box<dyn std::error::Error>> {
+    if !skip_compatibility_check {
+        doctor::ensure_postgres(database_url)?;
+    }
+    let conn = PgConnection::establish(database_url)?;
+    match command {
+        // doctor reports the problems itself
+        Command::Doctor => (),
+        _ if skip_compatibility_check => (),
+        _ => doctor::ensure_compatible(&conn)?,
+    }
+    Ok(conn)
+}
+
+fn fail(err: Box<dyn std::error::Error>) -> ! {
+    eprintln!("error: {}\n\n    {:?}\n", err, err);
+    std::process::exit(1);
+}
+
 fn main() {
     let args = Arguments::from_args();
-    let database = args.database_url.or_else(|| dotenv::var("DATABASE_URL").ok());
+    let command = args.command;
+    let skip_compatibility_check = args.skip_compatibility_check;
+    // Explicit arguments take precedence over the profile, which takes precedence over the environment
+    let profile = match args.profile.or_else(|| dotenv::var("METABASE_PROFILE").ok()) {
+        Some(name) => config::load_profile(&name, &secrets::env).unwrap_or_else(|err| fail(err)),
+        None => config::Profile::default(),
+    };
+    let creator = args.as_user.or_else(|| profile.as_user.clone());
+    let database = args.database_url
+        .or_else(|| profile.database_url.clone())
+        .or_else(|| dotenv::var("DATABASE_URL").ok());
+    // The api is only used to import and export when there's no database url the cli can read (e.g. a profile can
+    // have both, or metabase's database can be mysql/mariadb)
+    let api_url = args.api_url.or_else(|| profile.api_url.clone());
+    let (api_key, session) = (args.api_key, args.session);
+    let readable = database.as_deref().map(|url| !doctor::is_mysql(url)).unwrap_or(false);
+    let api = match (&command, readable, &api_url) {
+        (Command::Import { .. }, false, Some(url)) | (Command::Export { .. }, false, Some(url)) => Some(api::ApiOptions {
+            url: url.clone(),
+            api_key: api_key.or_else(|| profile.api_key.clone()).or_else(|| dotenv::var("METABASE_API_KEY").ok()),
+            session: session.or_else(|| dotenv::var("METABASE_SESSION").ok()),
+            username: dotenv::var("METABASE_USERNAME").ok(),
+            password: dotenv::var("METABASE_PASSWORD").ok(),
+        }),
+        _ => None,
+    };
     let database_url = match database {
         Some(url) => url,
+        None if api.is_some() => String::new(),
+        None if api_url.is_some() => fail(crate::error!("only `import` and `export` can use --api-url (the other commands need --database-url)")),
         None => {
-            eprintln!("The --database-url argument must be passed, or the DATABASE_URL environment variable must be set.");
+            eprintln!("The --database-url argument must be passed, the DATABASE_URL environment variable must be set, or a --profile with a database_url must be used.");
             std::process::exit(1);
         }
     };
-    let result = match args.command {
+    if profile.read_only && command.writes() {
+        fail(crate::error!("the `{}` profile is read-only", profile.name));
+    }
+    // Commands run on a single connection, which is checked first (the api is used without one)
+    let conn = match &api {
+        Some(_) => None,
+        None => Some(connect(&database_url, &command, skip_compatibility_check).unwrap_or_else(|err| fail(err))),
+    };
+    let conn = || conn.as_ref().ok_or(crate::error!("the api can't run this command"));
+    let result = (|| match command {
+        Command::Archive { kind, identifiers, yes } => {
+            lifecycle::archive(conn()?, &lifecycle::LifecycleOptions { kind, identifiers, yes }, true)
+        }
+        Command::Unarchive { kind, identifiers, yes } => {
+            lifecycle::archive(conn()?, &lifecycle::LifecycleOptions { kind, identifiers, yes }, false)
+        }
+        Command::Move {
+            kind,
+            identifiers,
+            to_collection,
+            create_collections,
+            yes,
+        } => lifecycle::move_items(
+            conn()?,
+            &lifecycle::LifecycleOptions { kind, identifiers, yes },
+            &commands::parse_collection_path(&to_collection),
+            create_collections,
+        ),
+        Command::Delete {
+            kind,
+            identifiers,
+            cascade,
+            yes,
+        } => lifecycle::delete(conn()?, &lifecycle::LifecycleOptions { kind, identifiers, yes }, cascade),
+        Command::Clone {
+            datasource,
+            identifier,
+            to_collection,
+            create_collections,
+            name,
+            deep,
+        } => commands::clone(conn()?, &datasource, &identifier, &commands::CloneOptions {
+            to_collection: commands::parse_collection_path(&to_collection),
+            create_collections,
+            name,
+            deep,
+            creator,
+        }),
         Command::Export {
             datasource: source,
             identifier: id,
-        } => commands::export(&database_url, &source, &id),
+            include,
+            exclude,
+        } => match &api {
+            Some(api) => api::export(api, &source, id.as_deref()),
+            None => commands::export(conn()?, &source, id.as_deref(), &commands::ExportOptions { include, exclude }),
+        },
+        Command::Check => check::check(conn()?),
+        Command::Doctor => doctor::doctor(conn()?),
+        Command::Impact { column } => impact::impact(conn()?, &column),
+        Command::List {
+            kind,
+            collection,
+            creator,
+            label,
+            archived,
+            json,
+        } => inventory::list(conn()?, &inventory::ListOptions {
+            kind,
+            collection: collection.map(|path| commands::parse_collection_path(&path)),
+            creator,
+            label,
+            archived,
+            json,
+        }),
+        Command::Stale { days, kind, ids, json } => stale::stale(conn()?, &stale::StaleOptions { days, kind, ids, json }),
+        Command::Usage {
+            from,
+            to,
+            by,
+            limit,
+            format,
+        } => usage::usage(conn()?, &usage::UsageOptions { from, to, by, limit, format }),
+        Command::Retarget {
+            from_table,
+            from_database,
+            to_table,
+            to_database,
+            questions,
+            copy,
+            into_collection,
+            create_collections,
+        } => commands::retarget(conn()?, &commands::RetargetOptions {
+            from_table,
+            from_database,
+            to_table,
+            to_database,
+            questions,
+            copy,
+            into_collection: into_collection.map(|path| commands::parse_collection_path(&path)),
+            create_collections,
+            creator,
+        }),
+        Command::RewriteField {
+            table,
+            database,
+            from,
+            to,
+            dry_run,
+        } => rewrite::rewrite_field(conn()?, &rewrite::RewriteOptions { table, database, from, to, dry_run }),
+        Command::Users { command } => match command {
+            UsersCommand::List { json } => users::list_users(conn()?, json),
+            UsersCommand::Deactivate { email } => users::deactivate_user(conn()?, &email),
+        },
+        Command::Groups { command } => match command {
+            GroupsCommand::List { members, json } => users::list_groups(conn()?, members, json),
+            GroupsCommand::AddMember { group, email } => users::add_member(conn()?, &group, &email),
+            GroupsCommand::RemoveMember { group, email } => users::remove_member(conn()?, &group, &email),
+            GroupsCommand::Import { filename, dry_run } => users::import_groups(conn()?, &filename, dry_run),
+        },
+        Command::Permissions { command } => match command {
+            PermissionsCommand::Export => permissions::export(conn()?),
+            PermissionsCommand::Diff { filename } => permissions::diff(conn()?, &filename),
+            PermissionsCommand::Apply { filename } => permissions::apply(conn()?, &filename, false, creator.as_deref()),
+        },
+        Command::CollectionPermissions { command } => match command {
+            PermissionsCommand::Export => permissions::export_collections(conn()?),
+            PermissionsCommand::Diff { filename } => permissions::apply_collections(conn()?, &filename, true, creator.as_deref()),
+            PermissionsCommand::Apply { filename } => permissions::apply_collections(conn()?, &filename, false, creator.as_deref()),
+        },
         Command::Import {
             filename,
-        } => commands::import(&database_url, &filename),
-    };
-    match result {
-        Ok(()) => (),
-        Err(err) => {
-            eprintln!("error: {}\n\n    {:?}\n", err, err);
-            std::process::exit(1);
-        }
+            map,
+            mappings,
+            into_collection,
+            create_collections,
+            with_embedding,
+            public_uuid,
+            yes,
+        } => import_options(profile.mappings.clone(), map, mappings)
+            .and_then(|mut options| {
+                if profile.production {
+                    lifecycle::confirm(&[format!("importing {} with the production profile `{}`", filename, profile.name)], yes)?;
+                }
+                options.into_collection = into_collection.map(|path| commands::parse_collection_path(&path));
+                options.create_collections = create_collections;
+                options.with_embedding = with_embedding;
+                options.public_uuid = public_uuid;
+                options.creator = creator;
+                match &api {
+                    Some(api) => api::import(api, &filename, &options),
+                    None => commands::import(conn()?, &filename, &options),
+                }
+            }),
+    })();
+    if let Err(err) = result {
+        fail(err);
     }
 }
@@ -0,0 +1,99 @@
+use std::collections::BTreeMap;
+use std::error::Error;
+
+/// Renames applied to a snapshot's dependencies while importing it into a different environment.
+///
+/// Mappings can be read from a toml file:
+///
+/// ```toml
+/// [database]
+/// analytics_staging = "analytics"
+///
+/// [schema]
+/// staging = "public"
+/// ```
+#[derive(Clone, Default, Deserialize)]
+pub struct NameMap {
+    #[serde(default)]
+    pub database: BTreeMap<String, String>,
+
+    #[serde(default)]
+    pub schema: BTreeMap<String, String>,
+}
+
+impl NameMap {
+    pub fn load(filename: &str) -> Result<NameMap, Box<dyn Error>> {
+        let contents = std::fs::read_to_string(filename)?;
+        let mappings = toml::from_str(&contents)?;
+        Ok(mappings)
+    }
+
+    /// Adds the mappings of another map, replacing any with the same names.
+    pub fn extend(&mut self, other: NameMap) {
+        self.database.extend(other.database);
+        self.schema.extend(other.schema);
+    }
+
+    /// Adds a mapping in the form `<kind>:<from>=<to>` (e.g. `database:analytics_staging=analytics`).
+    pub fn add(&mut self, rule: &str) -> Result<(), Box<dyn Error>> {
+        let invalid = || crate::error!("invalid mapping `{}` (expected `<database|schema>:<from>=<to>`)", rule);
+        let mut parts = rule.splitn(2, ':');
+        let kind = parts.next().ok_or_else(invalid)?;
+        let mut names = parts.next().ok_or_else(invalid)?.splitn(2, '=');
+        let from = names.next().filter(|x| !x.is_empty()).ok_or_else(invalid)?;
+        let to = names.next().filter(|x| !x.is_empty()).ok_or_else(invalid)?;
+        let map = match kind {
+            "database" => &mut self.database,
+            "schema" => &mut self.schema,
+            _ => return Err(invalid()),
+        };
+        map.insert(from.to_string(), to.to_string());
+        Ok(())
+    }
+
+    pub fn database<'a>(&'a self, name: &'a str) -> &'a str {
+        self.database.get(name).map(|x| x.as_str()).unwrap_or(name)
+    }
+
+    pub fn schema<'a>(&'a self, name: &'a str) -> &'a str {
+        self.schema.get(name).map(|x| x.as_str()).unwrap_or(name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn add_parses_rules() {
+        let mut mappings = NameMap::default();
+        mappings.add("database:analytics_staging=analytics").unwrap();
+        mappings.add("schema:staging=public").unwrap();
+        mappings.add("schema:a=b=c").unwrap();
+        assert_eq!(mappings.database("analytics_staging"), "analytics");
+        assert_eq!(mappings.database("other"), "other");
+        assert_eq!(mappings.schema("staging"), "public");
+        assert_eq!(mappings.schema("a"), "b=c");
+    }
+
+    #[test]
+    fn add_rejects_invalid_rules() {
+        let mut mappings = NameMap::default();
+        for rule in &["database", "database:from", "database:=to", "database:from=", "table:a=b"] {
+            assert!(mappings.add(rule).is_err(), "{}", rule);
+        }
+        assert!(mappings.database.is_empty() && mappings.schema.is_empty());
+    }
+
+    #[test]
+    fn extend_replaces_existing_names() {
+        let mut mappings: NameMap = toml::from_str("[database]\na = \"b\"\nc = \"d\"\n").unwrap();
+        let mut other = NameMap::default();
+        other.add("database:a=x").unwrap();
+        other.add("schema:s=t").unwrap();
+        mappings.extend(other);
+        assert_eq!(mappings.database("a"), "x");
+        assert_eq!(mappings.database("c"), "d");
+        assert_eq!(mappings.schema("s"), "t");
+    }
+}
@@ -39,18 +39,40 @@ impl Cache {
         Ok(Cache { collections, databases, tables, fields })
     }
 
+    /// Builds a cache from rows loaded elsewhere (e.g. from metabase's api).
+    pub fn new(collections: Vec<Collection>, databases: Vec<Database>, tables: Vec<DbTable>, fields: Vec<DbField>) -> Cache {
+        Cache {
+            collections: collections.into_iter().map(|row| (row.id, row)).collect(),
+            databases: databases.into_iter().map(|row| (row.id, row)).collect(),
+            tables: tables.into_iter().map(|row| (row.id, row)).collect(),
+            fields: fields.into_iter().map(|row| (row.id, row)).collect(),
+        }
+    }
+
+    pub fn collections(&self) -> impl Iterator<Item = &Collection> {
+        self.collections.values()
+    }
     pub fn collection(&self, id: i32) -> Result<&Collection, Box<dyn Error>> {
         self.collections.get(&id).ok_or(crate::error!("could not find collection (id: {})", id))
     }
+    pub fn databases(&self) -> impl Iterator<Item = &Database> {
+        self.databases.values()
+    }
     pub fn database(&self, id: i32) -> Result<&Database, Box<dyn Error>> {
         self.databases.get(&id).ok_or(crate::error!("could not find database (id: {})", id))
     }
+    pub fn tables(&self) -> impl Iterator<Item = &DbTable> {
+        self.tables.values()
+    }
     pub fn table(&self, id: i32) -> Result<&DbTable, Box<dyn Error>> {
         self.tables.get(&id).ok_or(crate::error!("could not find table (id: {})", id))
     }
     pub fn field(&self, id: i32) -> Result<&DbField, Box<dyn Error>> {
         self.fields.get(&id).ok_or(crate::error!("could not find field (id: {})", id))
     }
+    pub fn table_fields(&self, table_id: i32) -> impl Iterator<Item = &DbField> {
+        self.fields.values().filter(move |x| x.table_id == table_id)
+    }
     pub fn table_field(&self, table_id: i32, field: &str) -> Result<&DbField, Box<dyn Error>> {
         self.fields
             .values()
@@ -58,6 +80,54 @@ impl Cache {
             .next()
             .ok_or(crate::error!("could not find field on table (table: {}, field: {})", table_id, field))
     }
+    /// Returns the parents of a collection (from its `location`), starting from the root collection.
+    pub fn collection_ancestors(&self, collection: &Collection) -> Result<Vec<&Collection>, Box<dyn Error>> {
+        collection.location
+            .split('/')
+            .filter(|x| !x.is_empty())
+            .map(|x| self.collection(x.parse()?))
+            .collect()
+    }
+    /// Returns the names of a collection's parents followed by the collection's own name.
+    pub fn collection_path(&self, collection: &Collection) -> Result<Vec<String>, Box<dyn Error>> {
+        let mut path = self.collection_ancestors(collection)?
+            .into_iter()
+            .map(|x| x.name.clone())
+            .collect::<Vec<_>>();
+        path.push(collection.name.clone());
+        Ok(path)
+    }
+    /// Finds the (non-archived) collection at a path of collection names.
+    ///
+    /// A path with a single name falls back to matching any collection with that name, so that
+    /// snapshots which only recorded the collection's name can still be resolved when it is unique.
+    pub fn find_collection(&self, path: &[String]) -> Result<&Collection, Box<dyn Error>> {
+        let name = path.last().ok_or(crate::error!("collection path is empty"))?;
+        let candidates = self.search_collections(name)
+            .into_iter()
+            .filter(|x| !x.archived)
+            .collect::<Vec<_>>();
+        let mut matches = Vec::new();
+        for &candidate in &candidates {
+            if self.collection_path(candidate)? == path {
+                matches.push(candidate);
+            }
+        }
+        if matches.is_empty() && path.len() == 1 {
+            matches = candidates;
+        }
+        match matches.len() {
+            0 => Err(crate::error!("collection doesn't exist (collection: {})", path.join("/"))),
+            1 => Ok(matches[0]),
+            _ => {
+                let mut names = Vec::new();
+                for candidate in matches {
+                    names.push(format!("{} (id: {})", self.collection_path(candidate)?.join("/"), candidate.id));
+                }
+                Err(crate::error!("collection is ambiguous (collection: {}, candidates: {})", path.join("/"), names.join(", ")))
+            }
+        }
+    }
     pub fn search_collections(&self, collection: &str) -> Vec<&Collection> {
         self.collections.values().filter(|x| x.name == collection).collect()
     }
@@ -199,6 +269,144 @@ pub struct Question {
     pub collection_position: Option<i16>,
 }
 
+/// A segment, with the columns selected by `Segment::COLUMNS`.
+#[derive(Debug, Queryable)]
+pub struct Segment {
+    pub id: i32,
+    pub table_id: i32,
+    pub name: String,
+    pub archived: bool,
+    pub definition: String,
+}
+
+impl Segment {
+    pub const COLUMNS: (
+        schema::segment::id,
+        schema::segment::table_id,
+        schema::segment::name,
+        schema::segment::archived,
+        schema::segment::definition,
+    ) = (
+        schema::segment::id,
+        schema::segment::table_id,
+        schema::segment::name,
+        schema::segment::archived,
+        schema::segment::definition,
+    );
+}
+
+/// A metric, with the columns selected by `Metric::COLUMNS`.
+#[derive(Debug, Queryable)]
+pub struct Metric {
+    pub id: i32,
+    pub table_id: i32,
+    pub name: String,
+    pub archived: bool,
+    pub definition: String,
+}
+
+impl Metric {
+    pub const COLUMNS: (
+        schema::metric::id,
+        schema::metric::table_id,
+        schema::metric::name,
+        schema::metric::archived,
+        schema::metric::definition,
+    ) = (
+        schema::metric::id,
+        schema::metric::table_id,
+        schema::metric::name,
+        schema::metric::archived,
+        schema::metric::definition,
+    );
+}
+
+/// A pulse or an alert (alerts have an `alert_condition`), with the columns selected by `Pulse::COLUMNS`.
+#[derive(Debug, Queryable)]
+pub struct Pulse {
+    pub id: i32,
+    pub creator_id: i32,
+    pub name: Option<String>,
+    pub alert_condition: Option<String>,
+    pub collection_id: Option<i32>,
+    pub archived: Option<bool>,
+}
+
+impl Pulse {
+    pub const COLUMNS: (
+        schema::pulse::id,
+        schema::pulse::creator_id,
+        schema::pulse::name,
+        schema::pulse::alert_condition,
+        schema::pulse::collection_id,
+        schema::pulse::archived,
+    ) = (
+        schema::pulse::id,
+        schema::pulse::creator_id,
+        schema::pulse::name,
+        schema::pulse::alert_condition,
+        schema::pulse::collection_id,
+        schema::pulse::archived,
+    );
+
+    pub fn kind(&self) -> &'static str {
+        if self.alert_condition.is_some() { "alert" } else { "pulse" }
+    }
+}
+
+/// A user, with the columns selected by `User::COLUMNS` (credentials are never loaded).
+#[derive(Debug, Queryable)]
+pub struct User {
+    pub id: i32,
+    pub email: String,
+    pub first_name: String,
+    pub last_name: String,
+    pub last_login: Option<DateTime<Utc>>,
+    pub is_superuser: bool,
+    pub is_active: bool,
+}
+
+impl User {
+    pub const COLUMNS: (
+        schema::core_user::id,
+        schema::core_user::email,
+        schema::core_user::first_name,
+        schema::core_user::last_name,
+        schema::core_user::last_login,
+        schema::core_user::is_superuser,
+        schema::core_user::is_active,
+    ) = (
+        schema::core_user::id,
+        schema::core_user::email,
+        schema::core_user::first_name,
+        schema::core_user::last_name,
+        schema::core_user::last_login,
+        schema::core_user::is_superuser,
+        schema::core_user::is_active,
+    );
+}
+
+#[derive(Debug, Queryable)]
+pub struct Label {
+    pub id: i32,
+    pub name: String,
+    pub slug: String,
+    pub icon: Option<String>,
+}
+
+#[derive(Debug, Queryable)]
+pub struct PermissionsGroup {
+    pub id: i32,
+    pub name: String,
+}
+
+#[derive(Debug, Queryable)]
+pub struct Permission {
+    pub id: i32,
+    pub object: String,
+    pub group_id: i32,
+}
+
 #[derive(Debug, Queryable)]
 pub struct Database {
     pub id: i32,
@@ -272,3 +480,213 @@ pub struct DbField {
     pub has_field_values: Option<String>,
     pub settings: Option<String>,
 }
+
+/// The tables and columns the cli's queries select, filter on or write, which `doctor` requires of the application
+/// database (the rest of `schema.rs` is only reported). Models loaded whole (e.g. `Question`) use all their columns.
+/// `doctor`'s tests check that every `<table>::<column>` the modules reference is listed here.
+pub const USED_COLUMNS: &[(&str, &[&str])] = &[
+    ("activity", &["user_id", "model", "model_id", "timestamp"]),
+    ("card_label", &["card_id", "label_id"]),
+    ("collection", &["id", "name", "description", "color", "archived", "location", "personal_owner_id", "slug"]),
+    ("collection_revision", &["id", "before", "after", "user_id", "created_at", "remark"]),
+    ("core_session", &["user_id"]),
+    ("core_user", &["id", "email", "first_name", "last_name", "last_login", "is_superuser", "is_active", "updated_at"]),
+    ("dashboard_favorite", &["dashboard_id"]),
+    ("dashboardcard_series", &["id", "dashboardcard_id", "card_id"]),
+    ("dimension", &["id", "name", "field_id", "human_readable_field_id"]),
+    ("label", &["id", "name", "slug", "icon"]),
+    ("metabase_database", &[
+        "id", "created_at", "updated_at", "name", "description", "details", "engine", "is_sample", "is_full_sync",
+        "points_of_interest", "caveats", "metadata_sync_schedule", "cache_field_values_schedule", "timezone",
+        "is_on_demand", "options",
+    ]),
+    ("metabase_field", &[
+        "id", "created_at", "updated_at", "name", "base_type", "special_type", "active", "description",
+        "preview_display", "position", "table_id", "parent_id", "display_name", "visibility_type",
+        "fk_target_field_id", "last_analyzed", "points_of_interest", "caveats", "fingerprint", "fingerprint_version",
+        "database_type", "has_field_values", "settings",
+    ]),
+    ("metabase_table", &[
+        "id", "created_at", "updated_at", "name", "rows", "description", "entity_name", "entity_type", "active",
+        "db_id", "display_name", "visibility_type", "schema", "points_of_interest", "caveats",
+        "show_in_getting_started", "fields_hash",
+    ]),
+    ("metric", &["id", "table_id", "name", "archived", "definition"]),
+    ("metric_important_field", &["metric_id", "field_id"]),
+    ("permissions", &["id", "object", "group_id"]),
+    ("permissions_group", &["id", "name"]),
+    ("permissions_group_membership", &["user_id", "group_id"]),
+    ("permissions_revision", &["id", "before", "after", "user_id", "created_at", "remark"]),
+    ("pulse", &["id", "creator_id", "name", "alert_condition", "collection_id", "collection_position", "archived", "updated_at"]),
+    ("pulse_card", &["id", "pulse_id", "card_id"]),
+    ("pulse_channel", &["id", "pulse_id"]),
+    ("pulse_channel_recipient", &["pulse_channel_id"]),
+    ("report_card", &[
+        "id", "created_at", "updated_at", "name", "description", "display", "dataset_query", "visualization_settings",
+        "creator_id", "database_id", "table_id", "query_type", "archived", "collection_id", "public_uuid",
+        "made_public_by_id", "enable_embedding", "embedding_params", "cache_ttl", "result_metadata", "read_permissions",
+        "collection_position",
+    ]),
+    ("report_cardfavorite", &["card_id"]),
+    ("report_dashboard", &[
+        "id", "created_at", "updated_at", "name", "description", "creator_id", "parameters", "points_of_interest",
+        "caveats", "show_in_getting_started", "public_uuid", "made_public_by_id", "enable_embedding", "embedding_params",
+        "archived", "position", "collection_id", "collection_position",
+    ]),
+    ("report_dashboardcard", &[
+        "id", "created_at", "updated_at", "sizeX", "sizeY", "row", "col", "card_id", "dashboard_id",
+        "parameter_mappings", "visualization_settings",
+    ]),
+    ("revision", &["model", "model_id"]),
+    ("segment", &["id", "table_id", "name", "archived", "definition"]),
+    ("setting", &["key", "value"]),
+    ("view_log", &["user_id", "model", "model_id", "timestamp"]),
+];
+
+/// Rows for the unit tests of the modules working on a `Cache`.
+#[cfg(test)]
+pub mod fixtures {
+    use super::*;
+
+    pub fn database(id: i32, name: &str) -> Database {
+        Database {
+            id,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+            name: name.to_string(),
+            description: None,
+            details: None,
+            engine: "postgres".to_string(),
+            is_sample: false,
+            is_full_sync: true,
+            points_of_interest: None,
+            caveats: None,
+            metadata_sync_schedule: "0 50 * * * ? *".to_string(),
+            cache_field_values_schedule: "0 50 0 * * ? *".to_string(),
+            timezone: None,
+            is_on_demand: false,
+            options: None,
+        }
+    }
+
+    pub fn table(id: i32, db_id: i32, schema: &str, name: &str) -> DbTable {
+        DbTable {
+            id,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+            name: name.to_string(),
+            rows: None,
+            description: None,
+            entity_name: None,
+            entity_type: None,
+            active: true,
+            db_id,
+            display_name: None,
+            visibility_type: None,
+            schema: Some(schema.to_string()),
+            points_of_interest: None,
+            caveats: None,
+            show_in_getting_started: false,
+            fields_hash: None,
+        }
+    }
+
+    pub fn field(id: i32, table_id: i32, name: &str) -> DbField {
+        DbField {
+            id,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+            name: name.to_string(),
+            base_type: "type/Integer".to_string(),
+            special_type: None,
+            active: true,
+            description: None,
+            preview_display: true,
+            position: 0,
+            table_id,
+            parent_id: None,
+            display_name: None,
+            visibility_type: "normal".to_string(),
+            fk_target_field_id: None,
+            last_analyzed: None,
+            points_of_interest: None,
+            caveats: None,
+            fingerprint: None,
+            fingerprint_version: 0,
+            database_type: "int4".to_string(),
+            has_field_values: None,
+            settings: None,
+        }
+    }
+
+    pub fn collection(id: i32, name: &str, location: &str) -> Collection {
+        Collection {
+            id,
+            name: name.to_string(),
+            description: None,
+            color: "#509EE3".to_string(),
+            archived: false,
+            location: location.to_string(),
+            personal_owner_id: None,
+            slug: name.to_lowercase(),
+        }
+    }
+
+    /// A cache with the `analytics` database, its `public.orders` (fields 1-3) and `public.users` (fields 4-5) tables.
+    pub fn cache() -> Cache {
+        Cache::new(
+            Vec::new(),
+            vec![database(1, "analytics")],
+            vec![table(1, 1, "public", "orders"), table(2, 1, "public", "users")],
+            vec![field(1, 1, "id"), field(2, 1, "total"), field(3, 1, "created_at"), field(4, 2, "id"), field(5, 2, "email")],
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cache() -> Cache {
+        let collections = vec![
+            fixtures::collection(1, "Finance", "/"),
+            fixtures::collection(2, "Reports", "/1/"),
+            fixtures::collection(3, "Finance", "/1/2/"),
+            fixtures::collection(4, "Marketing", "/"),
+        ];
+        Cache::new(collections, Vec::new(), Vec::new(), Vec::new())
+    }
+
+    fn path(path: &str) -> Vec<String> {
+        path.split('/').map(|x| x.to_string()).collect()
+    }
+
+    #[test]
+    fn collection_path_includes_ancestors() {
+        let cache = cache();
+        assert_eq!(cache.collection_path(cache.collection(3).unwrap()).unwrap(), path("Finance/Reports/Finance"));
+    }
+
+    #[test]
+    fn find_collection_resolves_full_paths() {
+        let cache = cache();
+        assert_eq!(cache.find_collection(&path("Finance/Reports/Finance")).unwrap().id, 3);
+        assert_eq!(cache.find_collection(&path("Finance")).unwrap().id, 1);
+        assert_eq!(cache.find_collection(&path("Reports")).unwrap().id, 2);
+        assert!(cache.find_collection(&path("Marketing/Reports")).is_err());
+        assert!(cache.find_collection(&[]).is_err());
+    }
+
+    #[test]
+    fn find_collection_reports_ambiguous_names() {
+        let collections = vec![
+            fixtures::collection(1, "Team", "/"),
+            fixtures::collection(2, "Finance", "/1/"),
+            fixtures::collection(3, "Ops", "/"),
+            fixtures::collection(4, "Finance", "/3/"),
+        ];
+        let cache = Cache::new(collections, Vec::new(), Vec::new(), Vec::new());
+        assert!(cache.find_collection(&path("Finance")).is_err());
+        assert_eq!(cache.find_collection(&path("Ops/Finance")).unwrap().id, 4);
+    }
+}
@@ -0,0 +1,573 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::error::Error;
+
+use chrono::Utc;
+use diesel::prelude::*;
+use diesel::pg::PgConnection;
+use regex::Regex;
+
+use crate::commands::find_database;
+use crate::model::*;
+use crate::schema::*;
+use crate::snapshot::{CollectionPermissionsSnapshot, PermissionsSnapshot};
+use crate::users;
+
+/// A permission that is granted (or revoked) by applying a permissions snapshot.
+struct Change<'a> {
+    group: &'a PermissionsGroup,
+    object: String,
+    grant: bool,
+}
+
+/// Writes the permissions of every group, with names in place of the ids in the permission paths.
+///
+/// Collection permissions are included (as `/collection/[<path>]/` objects), as in snapshots written before
+/// [`export_collections`] existed; that command writes the same permissions keyed by collection instead.
+pub fn export(conn: &PgConnection) -> Result<(), Box<dyn Error>> {
+    let cache = Cache::load(conn)?;
+    let groups = permissions_group::table.get_results::<PermissionsGroup>(conn)?;
+    let permissions = permissions::table.get_results::<Permission>(conn)?;
+    let mut snapshot = PermissionsSnapshot { groups: BTreeMap::new() };
+    for group in &groups {
+        let objects = permissions.iter()
+            .filter(|x| x.group_id == group.id)
+            .map(|x| portable_object(&cache, &x.object))
+            .collect::<Result<_, _>>()?;
+        snapshot.groups.insert(group.name.clone(), objects);
+    }
+    // Permissions are meant to be reviewed in git, so each path is written on its own line
+    println!("{}", serde_json::to_string_pretty(&snapshot)?);
+    Ok(())
+}
+
+/// Prints the permissions that applying a snapshot would grant (`+`) and revoke (`-`).
+pub fn diff(conn: &PgConnection, filename: &str) -> Result<(), Box<dyn Error>> {
+    apply(conn, filename, true, None)
+}
+
+/// Makes the permissions of the groups in a snapshot match it, recording a `permissions_revision`.
+///
+/// Groups that aren't in the snapshot are left untouched.
+pub fn apply(conn: &PgConnection, filename: &str, dry_run: bool, creator: Option<&str>) -> Result<(), Box<dyn Error>> {
+    let contents = std::fs::read_to_string(filename)?;
+    let snapshot: PermissionsSnapshot = serde_json::from_str(&contents)?;
+    let cache = Cache::load(conn)?;
+    let groups = permissions_group::table.get_results::<PermissionsGroup>(conn)?;
+    let permissions = permissions::table.get_results::<Permission>(conn)?;
+
+    let mut changes = Vec::new();
+    for (name, objects) in &snapshot.groups {
+        let group = groups.iter()
+            .find(|x| &x.name == name)
+            .ok_or(crate::error!("group doesn't exist (group: {})", name))?;
+        let wanted = objects.iter()
+            .map(|x| resolve_object(&cache, x))
+            .collect::<Result<BTreeSet<_>, _>>()?;
+        let current = permissions.iter()
+            .filter(|x| x.group_id == group.id)
+            .map(|x| x.object.clone())
+            .collect::<BTreeSet<_>>();
+        for object in current.difference(&wanted) {
+            changes.push(Change { group, object: object.clone(), grant: false });
+        }
+        for object in wanted.difference(&current) {
+            changes.push(Change { group, object: object.clone(), grant: true });
+        }
+    }
+    for change in &changes {
+        let sign = if change.grant { "+" } else { "-" };
+        println!("{} {}: {}", sign, change.group.name, portable_object(&cache, &change.object)?);
+    }
+    if dry_run || changes.is_empty() {
+        eprintln!("{} permission changes", changes.len());
+        return Ok(());
+    }
+
+    conn.transaction::<_, Box<dyn Error>, _>(|| {
+        let revision = permissions_revision::table
+            .select(diesel::dsl::max(permissions_revision::id))
+            .get_result::<Option<i32>>(conn)?
+            .unwrap_or(0);
+        let before = graph(&cache, &groups, &permissions, revision);
+        for change in &changes {
+            if change.grant {
+                diesel::insert_into(permissions::table)
+                    .values((permissions::object.eq(&change.object), permissions::group_id.eq(change.group.id)))
+                    .execute(conn)?;
+            } else {
+                diesel::delete(permissions::table)
+                    .filter(permissions::object.eq(&change.object))
+                    .filter(permissions::group_id.eq(change.group.id))
+                    .execute(conn)?;
+            }
+        }
+        let permissions = permissions::table.get_results::<Permission>(conn)?;
+        let after = graph(&cache, &groups, &permissions, revision + 1);
+        diesel::insert_into(permissions_revision::table)
+            .values((
+                permissions_revision::before.eq(serde_json::to_string(&before)?),
+                permissions_revision::after.eq(serde_json::to_string(&after)?),
+                permissions_revision::user_id.eq(users::find_creator(conn, creator)?),
+                permissions_revision::created_at.eq(Utc::now().naive_utc()),
+                permissions_revision::remark.eq(format!("applied {}", filename)),
+            ))
+            .execute(conn)?;
+        Ok(())
+    })?;
+    eprintln!("applied {} permission changes", changes.len());
+    Ok(())
+}
+
+/// A change to a group's access level for a collection (`None` for the root collection).
+struct CollectionChange<'a> {
+    group: &'a PermissionsGroup,
+    collection_id: Option<i32>,
+    path: String,
+    before: &'static str,
+    after: &'static str,
+}
+
+/// Writes the access level of each group to each (non-personal, non-archived) collection.
+pub fn export_collections(conn: &PgConnection) -> Result<(), Box<dyn Error>> {
+    let cache = Cache::load(conn)?;
+    let groups = permissions_group::table.get_results::<PermissionsGroup>(conn)?;
+    let permissions = permissions::table.get_results::<Permission>(conn)?;
+    let mut collections = vec![(None, "/".to_string())];
+    for collection in cache.collections() {
+        if !collection.archived && !is_personal(&cache, collection)? {
+            let path = cache.collection_path(collection)?.iter().map(|x| escape_name(x)).collect::<Vec<_>>();
+            collections.push((Some(collection.id), path.join("/")));
+        }
+    }
+    let mut snapshot = CollectionPermissionsSnapshot { collections: BTreeMap::new() };
+    for (collection_id, path) in collections {
+        let levels = groups.iter()
+            .map(|group| (group.name.clone(), collection_level(&permissions, group.id, collection_id)))
+            .filter(|(_, level)| *level != "none")
+            .map(|(name, level)| (name, level.to_string()))
+            .collect();
+        snapshot.collections.insert(path, levels);
+    }
+    println!("{}", serde_json::to_string_pretty(&snapshot)?);
+    Ok(())
+}
+
+/// Makes the access levels of the collections in a snapshot match it, recording a `collection_revision`.
+///
+/// Collections that aren't in the snapshot are left untouched, so applying a snapshot twice changes nothing.
+pub fn apply_collections(conn: &PgConnection, filename: &str, dry_run: bool, creator: Option<&str>) -> Result<(), Box<dyn Error>> {
+    let contents = std::fs::read_to_string(filename)?;
+    let snapshot: CollectionPermissionsSnapshot = serde_json::from_str(&contents)?;
+    let cache = Cache::load(conn)?;
+    let groups = permissions_group::table.get_results::<PermissionsGroup>(conn)?;
+    let permissions = permissions::table.get_results::<Permission>(conn)?;
+
+    let mut changes = Vec::new();
+    for (path, levels) in &snapshot.collections {
+        let collection_path = unescape_path(path);
+        let collection_id = if collection_path.is_empty() {
+            None
+        } else {
+            let collection = cache.find_collection(&collection_path)?;
+            if is_personal(&cache, collection)? {
+                return Err(crate::error!("personal collections don't have permissions (collection: {})", path));
+            }
+            Some(collection.id)
+        };
+        for (name, level) in levels {
+            if !groups.iter().any(|x| &x.name == name) {
+                return Err(crate::error!("group doesn't exist (group: {})", name));
+            }
+            if !["read", "write", "none"].contains(&level.as_str()) {
+                return Err(crate::error!("invalid access level `{}` (expected read, write or none)", level));
+            }
+        }
+        for group in &groups {
+            let before = collection_level(&permissions, group.id, collection_id);
+            let after = match levels.get(&group.name).map(|x| x.as_str()) {
+                Some("read") => "read",
+                Some("write") => "write",
+                _ => "none",
+            };
+            if before != after {
+                changes.push(CollectionChange { group, collection_id, path: path.clone(), before, after });
+            }
+        }
+    }
+    for change in &changes {
+        println!("~ {}: {} {} -> {}", change.path, change.group.name, change.before, change.after);
+    }
+    if dry_run || changes.is_empty() {
+        eprintln!("{} collection permission changes", changes.len());
+        return Ok(());
+    }
+
+    conn.transaction::<_, Box<dyn Error>, _>(|| {
+        let revision = collection_revision::table
+            .select(diesel::dsl::max(collection_revision::id))
+            .get_result::<Option<i32>>(conn)?
+            .unwrap_or(0);
+        let before = collection_graph(&cache, &groups, &permissions, revision)?;
+        for change in &changes {
+            let objects = vec![collection_object(change.collection_id, "write"), collection_object(change.collection_id, "read")];
+            diesel::delete(permissions::table)
+                .filter(permissions::object.eq_any(objects))
+                .filter(permissions::group_id.eq(change.group.id))
+                .execute(conn)?;
+            if change.after != "none" {
+                diesel::insert_into(permissions::table)
+                    .values((
+                        permissions::object.eq(collection_object(change.collection_id, change.after)),
+                        permissions::group_id.eq(change.group.id),
+                    ))
+                    .execute(conn)?;
+            }
+        }
+        let permissions = permissions::table.get_results::<Permission>(conn)?;
+        let after = collection_graph(&cache, &groups, &permissions, revision + 1)?;
+        diesel::insert_into(collection_revision::table)
+            .values((
+                collection_revision::before.eq(serde_json::to_string(&before)?),
+                collection_revision::after.eq(serde_json::to_string(&after)?),
+                collection_revision::user_id.eq(users::find_creator(conn, creator)?),
+                collection_revision::created_at.eq(Utc::now().naive_utc()),
+                collection_revision::remark.eq(format!("applied {}", filename)),
+            ))
+            .execute(conn)?;
+        Ok(())
+    })?;
+    eprintln!("applied {} collection permission changes", changes.len());
+    Ok(())
+}
+
+/// Personal collections (and their descendants) are only accessible to their owner.
+fn is_personal(cache: &Cache, collection: &Collection) -> Result<bool, Box<dyn Error>> {
+    let ancestors = cache.collection_ancestors(collection)?;
+    Ok(collection.personal_owner_id.is_some() || ancestors.iter().any(|x| x.personal_owner_id.is_some()))
+}
+
+fn collection_object(collection_id: Option<i32>, level: &str) -> String {
+    let id = collection_id.map(|x| x.to_string()).unwrap_or_else(|| "root".to_string());
+    match level {
+        "read" => format!("/collection/{}/read/", id),
+        _ => format!("/collection/{}/", id),
+    }
+}
+
+fn collection_level(permissions: &[Permission], group_id: i32, collection_id: Option<i32>) -> &'static str {
+    let granted = |object: String| permissions.iter().any(|x| x.group_id == group_id && x.object == object);
+    if granted(collection_object(collection_id, "write")) {
+        "write"
+    } else if granted(collection_object(collection_id, "read")) {
+        "read"
+    } else {
+        "none"
+    }
+}
+
+/// The access level of each group (by id) to each (non-personal) collection (by id, or `root`), in the graph format
+/// metabase records in a `collection_revision`: `{"revision": 1, "groups": {"2": {"root": "read", "3": "none"}}}`.
+fn collection_graph(cache: &Cache, groups: &[PermissionsGroup], permissions: &[Permission], revision: i32) -> Result<serde_json::Value, Box<dyn Error>> {
+    let mut collections = vec![None];
+    for collection in cache.collections() {
+        if !is_personal(cache, collection)? {
+            collections.push(Some(collection.id));
+        }
+    }
+    let graph = groups.iter()
+        .map(|group| {
+            let levels = collections.iter()
+                .map(|&id| (id.map(|x| x.to_string()).unwrap_or_else(|| "root".to_string()), collection_level(permissions, group.id, id)))
+                .collect::<BTreeMap<_, _>>();
+            (group.id.to_string(), levels)
+        })
+        .collect::<BTreeMap<_, _>>();
+    Ok(serde_json::json!({ "revision": revision, "groups": graph }))
+}
+
+/// The data access of each group (by id) to each database (by id), in the graph format metabase records in a
+/// `permissions_revision`: `{"revision": 1, "groups": {"2": {"1": {"native": "write", "schemas": "all"}}}}`.
+///
+/// `schemas` is `all`, `none` or the access to each schema (by name), which is either `all` or the access to each
+/// table (by id): `all`, or the `read` and `query` (`all` or `segmented`) access that was granted separately.
+fn graph(cache: &Cache, groups: &[PermissionsGroup], permissions: &[Permission], revision: i32) -> serde_json::Value {
+    let graph = groups.iter()
+        .map(|group| {
+            let objects = permissions.iter()
+                .filter(|x| x.group_id == group.id)
+                .map(|x| x.object.as_str())
+                .collect::<BTreeSet<_>>();
+            let databases = cache.databases()
+                .map(|database| (database.id.to_string(), database_access(&objects, database.id)))
+                .collect::<BTreeMap<_, _>>();
+            (group.id.to_string(), databases)
+        })
+        .collect::<BTreeMap<_, _>>();
+    serde_json::json!({ "revision": revision, "groups": graph })
+}
+
+/// A group's access to a database, given the group's permission paths.
+fn database_access(objects: &BTreeSet<&str>, database_id: i32) -> serde_json::Value {
+    let prefix = format!("/db/{}/", database_id);
+    let full = objects.contains("/") || objects.contains(prefix.as_str());
+    let native = if full || objects.contains(format!("{}native/", prefix).as_str()) {
+        "write"
+    } else if objects.contains(format!("{}native/read/", prefix).as_str()) {
+        "read"
+    } else {
+        "none"
+    };
+    if full || objects.contains(format!("{}schema/", prefix).as_str()) {
+        return serde_json::json!({ "native": native, "schemas": "all" });
+    }
+
+    let table = Regex::new(r"^table/(\d+)/(.*)$").unwrap();
+    let mut schemas = serde_json::Map::new();
+    for object in objects.iter().filter_map(|x| x.strip_prefix(&format!("{}schema/", prefix))) {
+        let (schema, rest) = match object.find('/') {
+            Some(i) => (&object[..i], &object[i + 1..]),
+            None => continue,
+        };
+        let access = schemas.entry(schema).or_insert_with(|| serde_json::json!({}));
+        if rest.is_empty() {
+            *access = serde_json::json!("all");
+            continue;
+        }
+        let caps = match table.captures(rest) {
+            Some(caps) => caps,
+            None => continue,
+        };
+        let tables = match access.as_object_mut() {
+            Some(tables) => tables,
+            None => continue,
+        };
+        let access = tables.entry(&caps[1]).or_insert_with(|| serde_json::json!({}));
+        match (&caps[2], access.as_object_mut()) {
+            ("", _) => *access = serde_json::json!("all"),
+            ("read/", Some(levels)) => {
+                levels.insert("read".to_string(), serde_json::json!("all"));
+            }
+            ("query/", Some(levels)) => {
+                levels.insert("query".to_string(), serde_json::json!("all"));
+            }
+            ("query/segmented/", Some(levels)) => {
+                levels.entry("query").or_insert_with(|| serde_json::json!("segmented"));
+            }
+            _ => (),
+        }
+    }
+    let schemas = if schemas.is_empty() { serde_json::json!("none") } else { serde_json::Value::Object(schemas) };
+    serde_json::json!({ "native": native, "schemas": schemas })
+}
+
+/// Replaces the database, table and collection ids in a permission path with their (escaped) names.
+///
+/// Paths that reference something that no longer exists are kept as they are.
+fn portable_object(cache: &Cache, object: &str) -> Result<String, Box<dyn Error>> {
+    let database = Regex::new(r"^/db/(\d+)/(.*)$").unwrap();
+    let table = Regex::new(r"^schema/([^/]*)/table/(\d+)/(.*)$").unwrap();
+    let collection = Regex::new(r"^/collection/(\d+)/(.*)$").unwrap();
+    if let Some(caps) = database.captures(object) {
+        let database = match cache.database(caps[1].parse()?) {
+            Ok(database) => database,
+            Err(_) => return Ok(object.to_string()),
+        };
+        let mut rest = caps[2].to_string();
+        if let Some(caps) = table.captures(&caps[2]) {
+            match cache.table(caps[2].parse()?) {
+                Ok(table) => rest = format!("schema/{}/table/[{}]/{}", &caps[1], escape_name(&table.name), &caps[3]),
+                Err(_) => return Ok(object.to_string()),
+            }
+        }
+        return Ok(format!("/db/[{}]/{}", escape_name(&database.name), rest));
+    }
+    if let Some(caps) = collection.captures(object) {
+        let path = cache.collection(caps[1].parse()?).and_then(|x| cache.collection_path(x));
+        if let Ok(path) = path {
+            let path = path.iter().map(|x| escape_name(x)).collect::<Vec<_>>();
+            return Ok(format!("/collection/[{}]/{}", path.join("/"), &caps[2]));
+        }
+    }
+    Ok(object.to_string())
+}
+
+/// Replaces the database, table and collection names in a permission path with their ids.
+fn resolve_object(cache: &Cache, object: &str) -> Result<String, Box<dyn Error>> {
+    let database = Regex::new(r"^/db/\[((?:[^\]\\]|\\.)*)\]/(.*)$").unwrap();
+    let table = Regex::new(r"^schema/([^/]*)/table/\[((?:[^\]\\]|\\.)*)\]/(.*)$").unwrap();
+    let collection = Regex::new(r"^/collection/\[((?:[^\]\\]|\\.)*)\]/(.*)$").unwrap();
+    if let Some(caps) = database.captures(object) {
+        let database = find_database(cache, &unescape_name(&caps[1]))?;
+        let mut rest = caps[2].to_string();
+        if let Some(caps) = table.captures(&caps[2]) {
+            let name = unescape_name(&caps[2]);
+            // Tables without a schema have an empty schema in the permission path
+            let table = cache.tables()
+                .find(|x| x.db_id == database.id && x.name == name && x.schema.as_deref().unwrap_or("") == &caps[1])
+                .ok_or(crate::error!("table doesn't exist (tablename: {}.{}.{})", database.name, &caps[1], name))?;
+            rest = format!("schema/{}/table/{}/{}", &caps[1], table.id, &caps[3]);
+        }
+        return Ok(format!("/db/{}/{}", database.id, rest));
+    }
+    if let Some(caps) = collection.captures(object) {
+        let collection = cache.find_collection(&unescape_path(&caps[1]))?;
+        return Ok(format!("/collection/{}/{}", collection.id, &caps[2]));
+    }
+    Ok(object.to_string())
+}
+
+/// Prefixes `]` and `/` (which would end a `[<name>]` segment, or split a collection path) and `\` with a `\`.
+fn escape_name(name: &str) -> String {
+    let mut escaped = String::with_capacity(name.len());
+    for c in name.chars() {
+        if c == '\\' || c == ']' || c == '/' {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+fn unescape_name(escaped: &str) -> String {
+    let mut name = String::with_capacity(escaped.len());
+    let mut chars = escaped.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => name.extend(chars.next()),
+            c => name.push(c),
+        }
+    }
+    name
+}
+
+/// Splits an escaped collection path on its unescaped `/`s (so `/` is the root collection's empty path).
+fn unescape_path(escaped: &str) -> Vec<String> {
+    let mut names = vec![String::new()];
+    let mut chars = escaped.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => names.last_mut().unwrap().extend(chars.next()),
+            '/' => names.push(String::new()),
+            c => names.last_mut().unwrap().push(c),
+        }
+    }
+    names.into_iter().filter(|x| !x.is_empty()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::fixtures;
+
+    fn cache() -> Cache {
+        let mut personal = fixtures::collection(4, "Ada's Personal Collection", "/");
+        personal.personal_owner_id = Some(1);
+        let mut schemaless = fixtures::table(3, 1, "", "orders");
+        schemaless.schema = None;
+        Cache::new(
+            vec![fixtures::collection(1, "Finance", "/"), fixtures::collection(2, "EU/US", "/1/"), fixtures::collection(3, "Reports", "/1/2/"), personal],
+            vec![fixtures::database(1, "analytics"), fixtures::database(2, "a/b [eu]")],
+            vec![fixtures::table(1, 1, "public", "orders"), fixtures::table(2, 2, "public", "odd]name"), schemaless],
+            Vec::new(),
+        )
+    }
+
+    #[test]
+    fn portable_object_round_trips() {
+        let cache = cache();
+        for (object, portable) in &[
+            ("/db/1/", "/db/[analytics]/"),
+            ("/db/1/schema/public/", "/db/[analytics]/schema/public/"),
+            ("/db/1/schema/public/table/1/query/", "/db/[analytics]/schema/public/table/[orders]/query/"),
+            ("/db/2/schema/public/table/2/", r"/db/[a\/b [eu\]]/schema/public/table/[odd\]name]/"),
+            ("/db/1/schema//table/3/", "/db/[analytics]/schema//table/[orders]/"),
+            ("/collection/1/read/", "/collection/[Finance]/read/"),
+            ("/collection/3/", r"/collection/[Finance/EU\/US/Reports]/"),
+            ("/collection/root/", "/collection/root/"),
+        ] {
+            assert_eq!(portable_object(&cache, object).unwrap(), *portable);
+            assert_eq!(resolve_object(&cache, portable).unwrap(), *object);
+        }
+    }
+
+    #[test]
+    fn portable_object_keeps_unknown_ids() {
+        let cache = cache();
+        assert_eq!(portable_object(&cache, "/db/9/schema/public/").unwrap(), "/db/9/schema/public/");
+        assert_eq!(portable_object(&cache, "/db/1/schema/public/table/9/").unwrap(), "/db/1/schema/public/table/9/");
+        assert!(portable_object(&cache, "/db/99999999999/").is_err());
+        assert!(resolve_object(&cache, "/db/[missing]/").is_err());
+    }
+
+    #[test]
+    fn collection_graph_lists_every_group_and_collection() {
+        let cache = cache();
+        let groups = vec![PermissionsGroup { id: 1, name: "All Users".to_string() }, PermissionsGroup { id: 2, name: "Analysts".to_string() }];
+        let permission = |id: i32, object: &str, group_id: i32| Permission { id, object: object.to_string(), group_id };
+        let permissions = vec![
+            permission(1, "/collection/root/read/", 1),
+            permission(2, "/collection/1/read/", 2),
+            permission(3, "/collection/1/", 2),
+            permission(4, "/db/1/", 2),
+        ];
+        let graph = collection_graph(&cache, &groups, &permissions, 7).unwrap();
+        assert_eq!(graph, serde_json::json!({
+            "revision": 7,
+            "groups": {
+                "1": {"root": "read", "1": "none", "2": "none", "3": "none"},
+                "2": {"root": "none", "1": "write", "2": "none", "3": "none"},
+            },
+        }));
+    }
+
+    #[test]
+    fn graph_lists_every_group_and_database() {
+        let cache = cache();
+        let groups = vec![
+            PermissionsGroup { id: 1, name: "All Users".to_string() },
+            PermissionsGroup { id: 2, name: "Administrators".to_string() },
+            PermissionsGroup { id: 3, name: "Analysts".to_string() },
+        ];
+        let permission = |id: i32, object: &str, group_id: i32| Permission { id, object: object.to_string(), group_id };
+        let permissions = vec![
+            permission(1, "/db/1/", 1),
+            permission(2, "/", 2),
+            permission(3, "/db/1/native/", 3),
+            permission(4, "/db/1/schema/public/table/1/read/", 3),
+            permission(5, "/db/1/schema/public/table/1/query/segmented/", 3),
+            permission(6, "/db/1/schema//table/3/", 3),
+            permission(7, "/db/2/schema/public/", 3),
+            permission(8, "/collection/1/", 3),
+        ];
+        assert_eq!(graph(&cache, &groups, &permissions, 4), serde_json::json!({
+            "revision": 4,
+            "groups": {
+                "1": {
+                    "1": {"native": "write", "schemas": "all"},
+                    "2": {"native": "none", "schemas": "none"},
+                },
+                "2": {
+                    "1": {"native": "write", "schemas": "all"},
+                    "2": {"native": "write", "schemas": "all"},
+                },
+                "3": {
+                    "1": {"native": "write", "schemas": {"public": {"1": {"read": "all", "query": "segmented"}}, "": {"3": "all"}}},
+                    "2": {"native": "none", "schemas": {"public": "all"}},
+                },
+            },
+        }));
+    }
+
+    #[test]
+    fn unescape_path_splits_on_unescaped_slashes() {
+        assert_eq!(unescape_path(r"Finance/EU\/US"), vec!["Finance", "EU/US"]);
+        assert!(unescape_path("/").is_empty());
+    }
+
+    #[test]
+    fn escape_name_round_trips() {
+        for name in &["plain", "a/b", "x]y", r"back\slash", "trailing\\"] {
+            assert_eq!(unescape_name(&escape_name(name)), *name);
+        }
+    }
+}
@@ -0,0 +1,80 @@
+use regex::Regex;
+
+/// Returns the ids of the fields referenced by `["field-id",<id>]` clauses.
+///
+/// Visualization settings also store field references as json encoded strings
+/// (e.g. `"[\"ref\",[\"field-id\",12]]"`), so escaped clauses are matched too.
+pub fn field_ids(raw: &str) -> Vec<i32> {
+    let re = Regex::new(r#"\[\\?"field-id\\?",\s*(\d+)\]"#).unwrap();
+    unique(re.captures_iter(raw).map(|x| x[1].parse::<i32>().unwrap()))
+}
+
+/// Returns the ids of the tables used as the source of a query (or join).
+pub fn table_ids(raw: &str) -> Vec<i32> {
+    let re = Regex::new(r#""source-table":\s*(\d+)"#).unwrap();
+    unique(re.captures_iter(raw).map(|x| x[1].parse::<i32>().unwrap()))
+}
+
+/// Returns the ids of the questions used as the source of a query (i.e. `"source-table":"card__<id>"`).
+pub fn question_ids(raw: &str) -> Vec<i32> {
+    let re = Regex::new(r#""source-table":\s*"card__(\d+)""#).unwrap();
+    unique(re.captures_iter(raw).map(|x| x[1].parse::<i32>().unwrap()))
+}
+
+pub fn segment_ids(raw: &str) -> Vec<i32> {
+    let re = Regex::new(r#"\["segment",\s*(\d+)\]"#).unwrap();
+    unique(re.captures_iter(raw).map(|x| x[1].parse::<i32>().unwrap()))
+}
+
+pub fn metric_ids(raw: &str) -> Vec<i32> {
+    let re = Regex::new(r#"\["metric",\s*(\d+)\]"#).unwrap();
+    unique(re.captures_iter(raw).map(|x| x[1].parse::<i32>().unwrap()))
+}
+
+/// Replaces every reference to a field (including json encoded references) with a reference to another field.
+pub fn replace_field_id(raw: &str, from: i32, to: i32) -> String {
+    let re = Regex::new(&format!(r#"\[(\\?)"field-id(\\?)",(\s*){}\]"#, from)).unwrap();
+    re.replace_all(raw, format!(r#"[${{1}}"field-id${{2}}",${{3}}{}]"#, to).as_str()).to_string()
+}
+
+fn unique(ids: impl Iterator<Item = i32>) -> Vec<i32> {
+    let mut ids = ids.collect::<Vec<_>>();
+    ids.sort();
+    ids.dedup();
+    ids
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn field_ids_matches_plain_and_encoded_clauses() {
+        let query = r#"{"query":{"filter":["=",["field-id",12],5],"breakout":[["field-id", 3]]}}"#;
+        assert_eq!(field_ids(query), vec![3, 12]);
+        let settings = r#"{"column_settings":{"[\"ref\",[\"field-id\",7]]":{}},"graph.dimensions":["[\"field-id\",12]"]}"#;
+        assert_eq!(field_ids(settings), vec![7, 12]);
+        assert!(field_ids(r#"["fk->",["field-id","a"]]"#).is_empty());
+    }
+
+    #[test]
+    fn table_and_question_ids_match_sources() {
+        let query = r#"{"source-table": 4,"joins":[{"source-table":2},{"source-table":"card__9"}]}"#;
+        assert_eq!(table_ids(query), vec![2, 4]);
+        assert_eq!(question_ids(query), vec![9]);
+    }
+
+    #[test]
+    fn segment_and_metric_ids_match_clauses() {
+        let query = r#"{"filter":["and",["segment",5],["segment", 5]],"aggregation":[["metric",8]]}"#;
+        assert_eq!(segment_ids(query), vec![5]);
+        assert_eq!(metric_ids(query), vec![8]);
+    }
+
+    #[test]
+    fn replace_field_id_keeps_the_clause_format() {
+        let raw = r#"{"filter":["=",["field-id", 12],1],"ref":"[\"field-id\",12]","other":["field-id",120]}"#;
+        let expected = r#"{"filter":["=",["field-id", 30],1],"ref":"[\"field-id\",30]","other":["field-id",120]}"#;
+        assert_eq!(replace_field_id(raw, 12, 30), expected);
+    }
+}
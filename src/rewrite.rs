@@ -0,0 +1,132 @@
+use std::error::Error;
+
+use chrono::Utc;
+use diesel::prelude::*;
+use diesel::pg::PgConnection;
+
+use crate::commands::find_table;
+use crate::model::*;
+use crate::references;
+use crate::schema::*;
+
+pub struct RewriteOptions {
+    /// The table, as `<schema>.<table>` (or just `<table>` for the public schema)
+    pub table: String,
+
+    /// The name of the database, when the table name exists in more than one database
+    pub database: Option<String>,
+
+    /// The name of the field that is currently referenced
+    pub from: String,
+
+    /// The name of the field that should be referenced instead
+    pub to: String,
+
+    /// Whether to only print the changes (rather than writing them)
+    pub dry_run: bool,
+}
+
+/// Points every question and dashboard card that references one field of a table at another field.
+pub fn rewrite_field(conn: &PgConnection, options: &RewriteOptions) -> Result<(), Box<dyn Error>> {
+    let cache = Cache::load(conn)?;
+    let table = find_table(&cache, &options.table, options.database.as_deref())?;
+    let from = find_field(&cache, table, &options.from, false)?;
+    let to = find_field(&cache, table, &options.to, true)?;
+    if from.id == to.id {
+        return Err(crate::error!("the fields to rewrite are the same (field: {})", from.id));
+    }
+    let rewrite = |raw: &str| references::replace_field_id(raw, from.id, to.id);
+
+    let timestamp = Utc::now();
+    let mut changed = 0;
+    conn.transaction::<_, Box<dyn Error>, _>(|| {
+        for question in report_card::table.order(report_card::id).get_results::<Question>(conn)? {
+            let query = rewrite(&question.dataset_query);
+            let settings = rewrite(&question.visualization_settings);
+            if query == question.dataset_query && settings == question.visualization_settings {
+                continue;
+            }
+            let label = format!("question {} ({})", question.id, question.name);
+            print_diff(&label, "dataset_query", &question.dataset_query, &query);
+            print_diff(&label, "visualization_settings", &question.visualization_settings, &settings);
+            if !options.dry_run {
+                diesel::update(report_card::table.find(question.id))
+                    .set((
+                        report_card::dataset_query.eq(&query),
+                        report_card::visualization_settings.eq(&settings),
+                        report_card::updated_at.eq(timestamp),
+                    ))
+                    .execute(conn)?;
+            }
+            changed += 1;
+        }
+        for card in report_dashboardcard::table.order(report_dashboardcard::id).get_results::<DashboardCard>(conn)? {
+            let parameters = rewrite(&card.parameter_mappings);
+            let settings = rewrite(&card.visualization_settings);
+            if parameters == card.parameter_mappings && settings == card.visualization_settings {
+                continue;
+            }
+            let label = format!("dashboard {} card {}", card.dashboard_id, card.id);
+            print_diff(&label, "parameter_mappings", &card.parameter_mappings, &parameters);
+            print_diff(&label, "visualization_settings", &card.visualization_settings, &settings);
+            if !options.dry_run {
+                diesel::update(report_dashboardcard::table.find(card.id))
+                    .set((
+                        report_dashboardcard::parameter_mappings.eq(&parameters),
+                        report_dashboardcard::visualization_settings.eq(&settings),
+                        report_dashboardcard::updated_at.eq(timestamp),
+                    ))
+                    .execute(conn)?;
+            }
+            changed += 1;
+        }
+        Ok(())
+    })?;
+
+    let action = if options.dry_run { "would rewrite" } else { "rewrote" };
+    eprintln!("{} {} questions/dashboard cards (field {} -> field {})", action, changed, from.id, to.id);
+    Ok(())
+}
+
+fn print_diff(label: &str, column: &str, before: &str, after: &str) {
+    if before != after {
+        println!("{} {}", label, column);
+        println!("- {}", before);
+        println!("+ {}", after);
+    }
+}
+
+/// Finds a field of a table by name; when renamed, the table keeps a (now inactive) field with the old name.
+fn find_field<'a>(cache: &'a Cache, table: &DbTable, name: &str, active: bool) -> Result<&'a DbField, Box<dyn Error>> {
+    let fields = cache.table_fields(table.id)
+        .filter(|x| x.name == name && (x.active || !active))
+        .collect::<Vec<_>>();
+    match fields.len() {
+        0 => Err(crate::error!("could not find field on table (table: {}, field: {})", table.id, name)),
+        1 => Ok(fields[0]),
+        _ => Err(crate::error!("field name is ambiguous (table: {}, field: {})", table.id, name)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::fixtures;
+
+    #[test]
+    fn find_field_prefers_active_fields_for_targets() {
+        let mut old = fixtures::field(6, 1, "amount");
+        old.active = false;
+        let cache = Cache::new(
+            Vec::new(),
+            vec![fixtures::database(1, "analytics")],
+            vec![fixtures::table(1, 1, "public", "orders")],
+            vec![old, fixtures::field(7, 1, "total"), fixtures::field(8, 1, "amount")],
+        );
+        let table = cache.table(1).unwrap();
+        assert_eq!(find_field(&cache, table, "total", true).unwrap().id, 7);
+        assert_eq!(find_field(&cache, table, "amount", true).unwrap().id, 8);
+        assert!(find_field(&cache, table, "amount", false).is_err());
+        assert!(find_field(&cache, table, "missing", false).is_err());
+    }
+}
@@ -1,3 +1,4 @@
+use std::collections::{BTreeMap, BTreeSet};
 use std::error::Error;
 
 use crate::model::ChartType;
@@ -6,7 +7,9 @@ use crate::model::ChartType;
 #[serde(tag = "type", rename_all = "kebab-case")]
 pub enum Snapshot {
     Dashboard(DashboardSnapshot),
+    Database(DatabaseSnapshot),
     Question(QuestionSnapshot),
+    Settings(SettingsSnapshot),
 }
 
 #[derive(Deserialize, Serialize)]
@@ -22,6 +25,16 @@ pub struct DashboardSnapshot {
     pub collection: Option<CollectionDependency>,
 
     pub cards: Vec<CardSnapshot>,
+
+    /// The position the dashboard is pinned at in its collection
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub collection_position: Option<i16>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub embedding: Option<EmbeddingSnapshot>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub public_uuid: Option<String>,
 }
 
 #[derive(Deserialize, Serialize)]
@@ -60,6 +73,87 @@ pub struct QuestionSnapshot {
 
     #[serde(skip_serializing_if = "Option::is_none")]
     pub table: Option<TableDependency>,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub labels: Vec<LabelDependency>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cache_ttl: Option<i32>,
+
+    /// The position the question is pinned at in its collection
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub collection_position: Option<i16>,
+
+    /// The columns of the question's results, with table and field ids replaced by
+    /// `snapshot:table:<schema>.<table>` and `snapshot:table:<schema>.<table>:field:<name>`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub result_metadata: Option<SerializedSnapshot>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub embedding: Option<EmbeddingSnapshot>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub public_uuid: Option<String>,
+}
+
+/// The embedding config of a question or dashboard.
+#[derive(Deserialize, Serialize)]
+pub struct EmbeddingSnapshot {
+    pub enabled: bool,
+
+    /// Whether each parameter (by slug) is `enabled`, `locked` or `disabled` when embedded
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub params: Option<SerializedSnapshot>,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct DatabaseSnapshot {
+    pub name: String,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+
+    pub engine: String,
+
+    /// The connection details, with secrets replaced by `${ENV_VAR}` placeholders
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub details: Option<SerializedSnapshot>,
+
+    pub metadata_sync_schedule: String,
+
+    pub cache_field_values_schedule: String,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timezone: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub options: Option<SerializedSnapshot>,
+
+    pub is_full_sync: bool,
+
+    pub is_on_demand: bool,
+}
+
+/// Instance settings by key, with secrets replaced by `${MB_<KEY>}` placeholders.
+#[derive(Deserialize, Serialize)]
+pub struct SettingsSnapshot {
+    pub settings: BTreeMap<String, String>,
+}
+
+/// The permissions of each group, with the ids in the permission paths replaced by names.
+///
+/// e.g. `/db/3/schema/public/table/12/` is written as `/db/[analytics]/schema/public/table/[orders]/`
+#[derive(Deserialize, Serialize)]
+pub struct PermissionsSnapshot {
+    pub groups: BTreeMap<String, BTreeSet<String>>,
+}
+
+/// The access level (`read` or `write`) of each group to each collection, keyed by collection path (`/` for the root).
+///
+/// Groups that aren't listed for a collection have no access to it.
+#[derive(Deserialize, Serialize)]
+pub struct CollectionPermissionsSnapshot {
+    pub collections: BTreeMap<String, BTreeMap<String, String>>,
 }
 
 #[derive(Deserialize, Serialize)]
@@ -71,6 +165,10 @@ impl SerializedSnapshot {
         Ok(SerializedSnapshot(value))
     }
 
+    pub fn from_value(value: serde_json::Value) -> Self {
+        SerializedSnapshot(value)
+    }
+
     pub fn to_string(&self) -> String {
         serde_json::to_string(&self.0).expect("Bug! Failed to stringify snapshot.")
     }
@@ -79,6 +177,29 @@ impl SerializedSnapshot {
 #[derive(Deserialize, Serialize)]
 pub struct CollectionDependency {
     pub collection: String,
+
+    /// The names of the collection's parents, starting from the root collection
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub ancestors: Vec<String>,
+}
+
+impl CollectionDependency {
+    pub fn path(&self) -> Vec<String> {
+        let mut path = self.ancestors.clone();
+        path.push(self.collection.clone());
+        path
+    }
+}
+
+/// A label is identified by its slug; its name and icon are used when the label has to be created.
+#[derive(Deserialize, Serialize)]
+pub struct LabelDependency {
+    pub slug: String,
+
+    pub name: String,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub icon: Option<String>,
 }
 
 #[derive(Deserialize, Serialize)]
@@ -87,6 +208,25 @@ pub struct QuestionDependency {
 
     #[serde(skip_serializing_if = "Option::is_none")]
     pub collection: Option<String>,
+
+    /// The names of the parents of the question's collection, starting from the root collection
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub collection_ancestors: Vec<String>,
+
+    /// The id of the question, when the snapshot is restored into the instance it was taken from (e.g. by `clone`);
+    /// it isn't exported, as ids differ between instances
+    #[serde(skip)]
+    pub id: Option<i32>,
+}
+
+impl QuestionDependency {
+    pub fn collection_path(&self) -> Option<Vec<String>> {
+        self.collection.as_ref().map(|collection| {
+            let mut path = self.collection_ancestors.clone();
+            path.push(collection.clone());
+            path
+        })
+    }
 }
 
 #[derive(Deserialize, Serialize)]
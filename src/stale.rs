@@ -0,0 +1,127 @@
+use std::collections::BTreeMap;
+use std::error::Error;
+
+use chrono::{DateTime, Duration, Utc};
+use diesel::dsl::sql;
+use diesel::prelude::*;
+use diesel::pg::PgConnection;
+use diesel::sql_types::{Nullable, Timestamptz};
+
+use crate::model::*;
+use crate::schema::*;
+
+pub struct StaleOptions {
+    /// Items that haven't been viewed (or created) in this many days are stale
+    pub days: i64,
+
+    /// Only report items of this type (one of: question or dashboard)
+    pub kind: Option<String>,
+
+    /// Whether only the ids are written (one per line), e.g. to pipe into `archive`
+    pub ids: bool,
+
+    /// Whether the report is written as json
+    pub json: bool,
+}
+
+#[derive(Serialize)]
+struct StaleItem {
+    #[serde(rename = "type")]
+    kind: &'static str,
+    id: i32,
+    name: String,
+    collection: String,
+    owner: Option<String>,
+    created_at: DateTime<Utc>,
+    last_viewed_at: Option<DateTime<Utc>>,
+}
+
+/// Lists the (non-archived) questions and dashboards that nobody has viewed in a number of days.
+pub fn stale(conn: &PgConnection, options: &StaleOptions) -> Result<(), Box<dyn Error>> {
+    check_options(options)?;
+    let cache = Cache::load(conn)?;
+    let users = core_user::table
+        .select((core_user::id, core_user::email))
+        .get_results::<(i32, String)>(conn)?
+        .into_iter()
+        .collect::<BTreeMap<_, _>>();
+    let views = view_log::table
+        .group_by((view_log::model, view_log::model_id))
+        // diesel can't mix aggregate and grouped columns in a select, so the aggregate is written as sql
+        .select((view_log::model, view_log::model_id, sql::<Nullable<Timestamptz>>("max(timestamp)")))
+        .get_results::<(String, i32, Option<DateTime<Utc>>)>(conn)?
+        .into_iter()
+        .filter_map(|(model, id, timestamp)| timestamp.map(|x| ((model, id), x)))
+        .collect::<BTreeMap<_, _>>();
+
+    let cutoff = Utc::now() - Duration::days(options.days);
+    let listed = |kind: &str| options.kind.as_ref().map(|x| x == kind).unwrap_or(true);
+    let mut items = Vec::new();
+    let mut add = |kind: &'static str, model: &str, id: i32, name: String, collection_id: Option<i32>, creator_id: i32, created_at: DateTime<Utc>| -> Result<(), Box<dyn Error>> {
+        let last_viewed_at = views.get(&(model.to_string(), id)).cloned();
+        if last_viewed_at.unwrap_or(created_at) >= cutoff {
+            return Ok(());
+        }
+        let collection = match collection_id {
+            Some(collection_id) => cache.collection_path(cache.collection(collection_id)?)?.join("/"),
+            None => "/".to_string(),
+        };
+        items.push(StaleItem { kind, id, name, collection, owner: users.get(&creator_id).cloned(), created_at, last_viewed_at });
+        Ok(())
+    };
+    if listed("question") {
+        for question in report_card::table.filter(report_card::archived.eq(false)).get_results::<Question>(conn)? {
+            add("question", "card", question.id, question.name, question.collection_id, question.creator_id, question.created_at)?;
+        }
+    }
+    if listed("dashboard") {
+        for dashboard in report_dashboard::table.filter(report_dashboard::archived.eq(false)).get_results::<Dashboard>(conn)? {
+            add("dashboard", "dashboard", dashboard.id, dashboard.name, dashboard.collection_id, dashboard.creator_id, dashboard.created_at)?;
+        }
+    }
+    // The longest unused items (never viewed first) are listed first
+    items.sort_by_key(|x| (x.last_viewed_at, x.created_at, x.id));
+
+    if options.ids {
+        for item in &items {
+            println!("{}", item.id);
+        }
+    } else if options.json {
+        println!("{}", serde_json::to_string(&items)?);
+    } else {
+        for item in &items {
+            let last_viewed = match item.last_viewed_at {
+                Some(timestamp) => format!("last viewed: {}", timestamp.format("%Y-%m-%d")),
+                None => "never viewed".to_string(),
+            };
+            println!(
+                "{} {} ({}, collection: {}, owner: {}, {})",
+                item.kind, item.id, item.name, item.collection, item.owner.as_deref().unwrap_or("unknown"), last_viewed,
+            );
+        }
+    }
+    eprintln!("{} items not viewed in {} days", items.len(), options.days);
+    Ok(())
+}
+
+fn check_options(options: &StaleOptions) -> Result<(), Box<dyn Error>> {
+    match options.kind.as_deref() {
+        Some("question") | Some("dashboard") => Ok(()),
+        None if !options.ids => Ok(()),
+        None => Err(crate::error!("--ids needs a --type, as the ids of questions and dashboards overlap")),
+        Some(kind) => Err(crate::error!("unknown item type `{}` (expected question or dashboard)", kind)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_options_rejects_invalid_options() {
+        let options = |kind: Option<&str>, ids: bool| StaleOptions { days: 90, kind: kind.map(|x| x.to_string()), ids, json: false };
+        let error = |options: StaleOptions| check_options(&options).unwrap_err().to_string();
+        assert!(error(options(None, true)).contains("--ids needs a --type"));
+        assert!(error(options(Some("pulse"), false)).contains("unknown item type"));
+    }
+}
@@ -0,0 +1,273 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::error::Error;
+
+use chrono::{DateTime, Duration, NaiveDate, TimeZone, Utc};
+use diesel::dsl::sql;
+use diesel::prelude::*;
+use diesel::pg::PgConnection;
+use diesel::sql_types::BigInt;
+
+use crate::model::*;
+use crate::schema::*;
+
+pub struct UsageOptions {
+    /// The first day of the report, as `YYYY-MM-DD` (defaults to 30 days before `to`)
+    pub from: Option<String>,
+
+    /// The day after the report, as `YYYY-MM-DD` (defaults to tomorrow, so that today is included)
+    pub to: Option<String>,
+
+    /// What the views are grouped by (one of: dashboard, question, user or collection)
+    pub by: String,
+
+    /// The number of rows to report
+    pub limit: usize,
+
+    /// The output format (one of: table, csv or json)
+    pub format: String,
+}
+
+#[derive(Default, Serialize)]
+struct UsageRow {
+    #[serde(rename = "type")]
+    kind: &'static str,
+    id: Option<i32>,
+    name: String,
+    views: usize,
+    /// The number of distinct users that viewed the object (not reported when grouping by user)
+    viewers: Option<usize>,
+    activities: usize,
+    previous_views: usize,
+    /// The change in views compared to the previous period of the same length, as a percentage
+    growth: Option<f64>,
+    #[serde(skip)]
+    users: BTreeSet<i32>,
+}
+
+/// The number of views of an object by one user in a period.
+struct Views {
+    user_id: Option<i32>,
+    model: String,
+    model_id: i32,
+    count: i64,
+}
+
+/// Summarises the views and activity of dashboards, questions, users or collections over a date range.
+///
+/// Growth compares the views to the previous period of the same length (e.g. the 30 days before the range).
+pub fn usage(conn: &PgConnection, options: &UsageOptions) -> Result<(), Box<dyn Error>> {
+    let kind = match options.by.as_str() {
+        "dashboard" => "dashboard",
+        "question" => "question",
+        "user" => "user",
+        "collection" => "collection",
+        _ => return Err(crate::error!("unknown grouping `{}` (expected dashboard, question, user or collection)", options.by)),
+    };
+    match options.format.as_str() {
+        "table" | "csv" | "json" => (),
+        _ => return Err(crate::error!("unknown format `{}` (expected table, csv or json)", options.format)),
+    }
+    let to = match &options.to {
+        Some(date) => parse_date(date)?,
+        None => parse_date(&(Utc::now() + Duration::days(1)).format("%Y-%m-%d").to_string())?,
+    };
+    let from = match &options.from {
+        Some(date) => parse_date(date)?,
+        None => to - Duration::days(30),
+    };
+    if from >= to {
+        return Err(crate::error!("the start of the range must be before its end (from: {}, to: {})", from.date().naive_utc(), to.date().naive_utc()));
+    }
+    let previous = from - (to - from);
+
+    let cache = Cache::load(conn)?;
+    let users = core_user::table
+        .select((core_user::id, core_user::email))
+        .get_results::<(i32, String)>(conn)?
+        .into_iter()
+        .collect::<BTreeMap<_, _>>();
+    let questions = report_card::table
+        .select((report_card::id, report_card::name, report_card::collection_id))
+        .get_results::<(i32, String, Option<i32>)>(conn)?
+        .into_iter()
+        .map(|(id, name, collection_id)| (id, (name, collection_id)))
+        .collect::<BTreeMap<_, _>>();
+    let dashboards = report_dashboard::table
+        .select((report_dashboard::id, report_dashboard::name, report_dashboard::collection_id))
+        .get_results::<(i32, String, Option<i32>)>(conn)?
+        .into_iter()
+        .map(|(id, name, collection_id)| (id, (name, collection_id)))
+        .collect::<BTreeMap<_, _>>();
+    // The views are counted by the database (per object and user), as view_log grows with every page load
+    let count_views = |start: DateTime<Utc>, end: DateTime<Utc>| -> Result<Vec<Views>, Box<dyn Error>> {
+        let counts = view_log::table
+            .filter(view_log::timestamp.ge(start))
+            .filter(view_log::timestamp.lt(end))
+            .group_by((view_log::user_id, view_log::model, view_log::model_id))
+            // diesel can't mix aggregate and grouped columns in a select, so the aggregate is written as sql
+            .select((view_log::user_id, view_log::model, view_log::model_id, sql::<BigInt>("count(*)")))
+            .get_results::<(Option<i32>, String, i32, i64)>(conn)?;
+        Ok(counts.into_iter().map(|(user_id, model, model_id, count)| Views { user_id, model, model_id, count }).collect())
+    };
+    let views = count_views(from, to)?;
+    let previous_views = count_views(previous, from)?;
+    let activities = activity::table
+        .filter(activity::timestamp.ge(from))
+        .filter(activity::timestamp.lt(to))
+        .group_by((activity::user_id, activity::model, activity::model_id))
+        .select((activity::user_id, activity::model, activity::model_id, sql::<BigInt>("count(*)")))
+        .get_results::<(Option<i32>, Option<String>, Option<i32>, i64)>(conn)?;
+
+    // Finds the row that a view (or activity) of an object is counted in
+    let key = |user_id: Option<i32>, model: &str, model_id: i32| -> Option<Option<i32>> {
+        let item = match model {
+            "card" => questions.get(&model_id),
+            "dashboard" => dashboards.get(&model_id),
+            _ => None,
+        };
+        match kind {
+            "dashboard" if model == "dashboard" => Some(Some(model_id)),
+            "question" if model == "card" => Some(Some(model_id)),
+            "collection" => item.map(|(_, collection_id)| *collection_id),
+            "user" => user_id.map(Some),
+            _ => None,
+        }
+    };
+    let mut rows: BTreeMap<Option<i32>, UsageRow> = BTreeMap::new();
+    let (mut total, mut total_previous, mut active) = (0, 0, BTreeSet::new());
+    for views in previous_views {
+        total_previous += views.count as usize;
+        if let Some(id) = key(views.user_id, &views.model, views.model_id) {
+            rows.entry(id).or_default().previous_views += views.count as usize;
+        }
+    }
+    for views in views {
+        total += views.count as usize;
+        active.extend(views.user_id);
+        if let Some(id) = key(views.user_id, &views.model, views.model_id) {
+            let row = rows.entry(id).or_default();
+            row.views += views.count as usize;
+            row.users.extend(views.user_id);
+        }
+    }
+    for (user_id, model, model_id, count) in activities {
+        active.extend(user_id);
+        let id = match (kind, model, model_id) {
+            ("user", _, _) => user_id.map(Some),
+            (_, Some(model), Some(model_id)) => key(user_id, &model, model_id),
+            _ => None,
+        };
+        if let Some(id) = id {
+            rows.entry(id).or_default().activities += count as usize;
+        }
+    }
+
+    let mut rows = rows.into_iter()
+        .map(|(id, mut row)| -> Result<UsageRow, Box<dyn Error>> {
+            row.kind = kind;
+            row.id = id;
+            row.name = match (kind, id) {
+                ("dashboard", Some(id)) => dashboards.get(&id).map(|x| x.0.clone()).unwrap_or_default(),
+                ("question", Some(id)) => questions.get(&id).map(|x| x.0.clone()).unwrap_or_default(),
+                ("user", Some(id)) => users.get(&id).cloned().unwrap_or_default(),
+                ("collection", Some(id)) => cache.collection_path(cache.collection(id)?)?.join("/"),
+                _ => "/".to_string(),
+            };
+            if kind != "user" {
+                row.viewers = Some(row.users.len());
+            }
+            row.growth = growth(row.views, row.previous_views);
+            Ok(row)
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    rows.sort_by(|a, b| (b.views, b.activities).cmp(&(a.views, a.activities)).then(a.name.cmp(&b.name)));
+    rows.truncate(options.limit);
+
+    match options.format.as_str() {
+        "json" => println!("{}", serde_json::to_string(&rows)?),
+        "csv" => {
+            println!("type,id,name,views,viewers,activities,previous_views,growth");
+            for row in &rows {
+                println!(
+                    "{},{},{},{},{},{},{},{}",
+                    row.kind,
+                    row.id.map(|x| x.to_string()).unwrap_or_default(),
+                    csv_field(&row.name),
+                    row.views,
+                    row.viewers.map(|x| x.to_string()).unwrap_or_default(),
+                    row.activities,
+                    row.previous_views,
+                    row.growth.map(|x| format!("{:.1}", x)).unwrap_or_default(),
+                );
+            }
+        }
+        _ => {
+            let width = rows.iter().map(|x| x.name.chars().count()).max().unwrap_or(0).max(4);
+            println!("{:<6} {:<width$} {:>7} {:>7} {:>10} {:>8} {:>8}", "id", "name", "views", "viewers", "activities", "previous", "growth", width = width);
+            for row in &rows {
+                println!(
+                    "{:<6} {:<width$} {:>7} {:>7} {:>10} {:>8} {:>8}",
+                    row.id.map(|x| x.to_string()).unwrap_or_else(|| "-".to_string()),
+                    row.name,
+                    row.views,
+                    row.viewers.map(|x| x.to_string()).unwrap_or_else(|| "-".to_string()),
+                    row.activities,
+                    row.previous_views,
+                    row.growth.map(|x| format!("{:+.1}%", x)).unwrap_or_else(|| "-".to_string()),
+                    width = width,
+                );
+            }
+        }
+    }
+    let growth = growth(total, total_previous).map(|x| format!("{:+.1}%", x)).unwrap_or_else(|| "n/a".to_string());
+    eprintln!(
+        "{} views by {} active users from {} to {} (growth: {})",
+        total, active.len(), from.date().naive_utc(), (to - Duration::days(1)).date().naive_utc(), growth,
+    );
+    Ok(())
+}
+
+fn parse_date(date: &str) -> Result<DateTime<Utc>, Box<dyn Error>> {
+    let date = NaiveDate::parse_from_str(date, "%Y-%m-%d")
+        .map_err(|_| crate::error!("invalid date `{}` (expected YYYY-MM-DD)", date))?;
+    Ok(Utc.from_utc_datetime(&date.and_hms(0, 0, 0)))
+}
+
+fn growth(views: usize, previous_views: usize) -> Option<f64> {
+    if previous_views == 0 {
+        return None;
+    }
+    Some((views as f64 - previous_views as f64) / previous_views as f64 * 100.0)
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains(&[',', '"', '\n'][..]) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_date_expects_iso_dates() {
+        assert_eq!(parse_date("2021-03-01").unwrap(), Utc.ymd(2021, 3, 1).and_hms(0, 0, 0));
+        assert!(parse_date("01/03/2021").is_err());
+    }
+
+    #[test]
+    fn growth_is_relative_to_the_previous_period() {
+        assert_eq!(growth(15, 10), Some(50.0));
+        assert_eq!(growth(5, 10), Some(-50.0));
+        assert_eq!(growth(5, 0), None);
+    }
+
+    #[test]
+    fn csv_field_quotes_special_characters() {
+        assert_eq!(csv_field("Revenue"), "Revenue");
+        assert_eq!(csv_field("Finance, \"EU\""), "\"Finance, \"\"EU\"\"\"");
+    }
+}
@@ -0,0 +1,346 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::error::Error;
+
+use chrono::Utc;
+use diesel::prelude::*;
+use diesel::pg::PgConnection;
+
+use crate::model::*;
+use crate::schema::*;
+
+/// Every user is a member of this group, so metabase manages its memberships itself.
+const ALL_USERS_GROUP: &str = "All Users";
+
+/// Members of this group are superusers (and superusers are members of it).
+const ADMINISTRATORS_GROUP: &str = "Administrators";
+
+/// A group membership file, mapping group names to the emails of their members.
+///
+/// ```yaml
+/// groups:
+///   Analysts:
+///     - alice@example.com
+///     - bob@example.com
+/// ```
+#[derive(Deserialize)]
+struct MembershipFile {
+    groups: BTreeMap<String, Vec<String>>,
+}
+
+#[derive(Serialize)]
+struct UserRow {
+    id: i32,
+    email: String,
+    name: String,
+    active: bool,
+    superuser: bool,
+    last_login: Option<String>,
+    groups: Vec<String>,
+}
+
+#[derive(Serialize)]
+struct GroupRow {
+    id: i32,
+    name: String,
+    members: Vec<String>,
+}
+
+pub fn list_users(conn: &PgConnection, json: bool) -> Result<(), Box<dyn Error>> {
+    let groups = load_groups(conn)?;
+    let users = core_user::table.order(core_user::email).select(User::COLUMNS).get_results::<User>(conn)?;
+    let rows = users.into_iter()
+        .map(|user| UserRow {
+            id: user.id,
+            name: format!("{} {}", user.first_name, user.last_name),
+            active: user.is_active,
+            superuser: user.is_superuser,
+            last_login: user.last_login.map(|x| x.format("%Y-%m-%d %H:%M").to_string()),
+            groups: groups.iter().filter(|x| x.members.contains(&user.email)).map(|x| x.name.clone()).collect(),
+            email: user.email,
+        })
+        .collect::<Vec<_>>();
+    if json {
+        println!("{}", serde_json::to_string(&rows)?);
+        return Ok(());
+    }
+    for row in rows {
+        let mut details = vec![format!("id: {}", row.id), row.name];
+        if !row.active {
+            details.push("deactivated".to_string());
+        }
+        if row.superuser {
+            details.push("superuser".to_string());
+        }
+        details.push(format!("last login: {}", row.last_login.as_deref().unwrap_or("never")));
+        if !row.groups.is_empty() {
+            details.push(format!("groups: {}", row.groups.join(", ")));
+        }
+        println!("{} ({})", row.email, details.join(", "));
+    }
+    Ok(())
+}
+
+/// Deactivates a user (so they can no longer log in), and ends their sessions.
+pub fn deactivate_user(conn: &PgConnection, email: &str) -> Result<(), Box<dyn Error>> {
+    let user = find_user(conn, email)?;
+    if !user.is_active {
+        return Err(crate::error!("user is already deactivated (email: {})", user.email));
+    }
+    if user.is_superuser && other_admins(conn, &user)? == 0 {
+        return Err(crate::error!("can't deactivate the last administrator (email: {})", user.email));
+    }
+    conn.transaction::<_, Box<dyn Error>, _>(|| {
+        diesel::update(core_user::table.find(user.id))
+            .set((core_user::is_active.eq(false), core_user::updated_at.eq(Utc::now().naive_utc())))
+            .execute(conn)?;
+        diesel::delete(core_session::table.filter(core_session::user_id.eq(user.id))).execute(conn)?;
+        Ok(())
+    })?;
+    eprintln!("deactivated {}", user.email);
+    Ok(())
+}
+
+pub fn list_groups(conn: &PgConnection, members: bool, json: bool) -> Result<(), Box<dyn Error>> {
+    let groups = load_groups(conn)?;
+    if json {
+        println!("{}", serde_json::to_string(&groups)?);
+        return Ok(());
+    }
+    for group in groups {
+        println!("{} (id: {}, members: {})", group.name, group.id, group.members.len());
+        if members {
+            for email in &group.members {
+                println!("  {}", email);
+            }
+        }
+    }
+    Ok(())
+}
+
+pub fn add_member(conn: &PgConnection, group: &str, email: &str) -> Result<(), Box<dyn Error>> {
+    let group = find_group(conn, group)?;
+    let user = find_user(conn, email)?;
+    conn.transaction(|| add_membership(conn, &group, &user))?;
+    eprintln!("added {} to {}", user.email, group.name);
+    Ok(())
+}
+
+pub fn remove_member(conn: &PgConnection, group: &str, email: &str) -> Result<(), Box<dyn Error>> {
+    let group = find_group(conn, group)?;
+    let user = find_user(conn, email)?;
+    conn.transaction(|| remove_membership(conn, &group, &user))?;
+    eprintln!("removed {} from {}", user.email, group.name);
+    Ok(())
+}
+
+/// Reconciles the members of the groups in a yaml/json file with the file (creating missing groups).
+///
+/// Groups that aren't in the file are left untouched.
+pub fn import_groups(conn: &PgConnection, filename: &str, dry_run: bool) -> Result<(), Box<dyn Error>> {
+    let file = parse_membership_file(filename, &std::fs::read_to_string(filename)?)?;
+    let users = core_user::table.select(User::COLUMNS).get_results::<User>(conn)?;
+    let find_user = |email: &str| {
+        users.iter()
+            .find(|x| x.email.eq_ignore_ascii_case(email))
+            .ok_or(crate::error!("user doesn't exist (email: {})", email))
+    };
+
+    let mut changes = 0;
+    conn.transaction::<_, Box<dyn Error>, _>(|| {
+        for (name, emails) in &file.groups {
+            if name == ALL_USERS_GROUP {
+                return Err(crate::error!("the members of `{}` are managed by metabase", ALL_USERS_GROUP));
+            }
+            let wanted = emails.iter().map(|x| find_user(x).map(|x| x.id)).collect::<Result<BTreeSet<_>, _>>()?;
+            let group = permissions_group::table
+                .filter(permissions_group::name.eq(name))
+                .get_result::<PermissionsGroup>(conn)
+                .optional()?;
+            let group = match group {
+                Some(group) => group,
+                None => {
+                    println!("+ group {}", name);
+                    changes += 1;
+                    if dry_run {
+                        for user in users.iter().filter(|x| wanted.contains(&x.id)) {
+                            println!("+ {}: {}", name, user.email);
+                            changes += 1;
+                        }
+                        continue;
+                    }
+                    diesel::insert_into(permissions_group::table)
+                        .values(permissions_group::name.eq(name))
+                        .get_result::<PermissionsGroup>(conn)?
+                }
+            };
+            let members = permissions_group_membership::table
+                .select(permissions_group_membership::user_id)
+                .filter(permissions_group_membership::group_id.eq(group.id))
+                .get_results::<i32>(conn)?
+                .into_iter()
+                .collect::<BTreeSet<_>>();
+            for user in users.iter().filter(|x| wanted.contains(&x.id) && !members.contains(&x.id)) {
+                println!("+ {}: {}", name, user.email);
+                if !dry_run {
+                    add_membership(conn, &group, user)?;
+                }
+                changes += 1;
+            }
+            for user in users.iter().filter(|x| members.contains(&x.id) && !wanted.contains(&x.id)) {
+                println!("- {}: {}", name, user.email);
+                if !dry_run {
+                    remove_membership(conn, &group, user)?;
+                }
+                changes += 1;
+            }
+        }
+        Ok(())
+    })?;
+    let action = if dry_run { "would make" } else { "made" };
+    eprintln!("{} {} group membership changes", action, changes);
+    Ok(())
+}
+
+/// Parses a membership file as yaml (for `.yaml`/`.yml` files) or json.
+fn parse_membership_file(filename: &str, contents: &str) -> Result<MembershipFile, Box<dyn Error>> {
+    if filename.ends_with(".yaml") || filename.ends_with(".yml") {
+        Ok(serde_yaml::from_str(contents)?)
+    } else {
+        Ok(serde_json::from_str(contents)?)
+    }
+}
+
+fn add_membership(conn: &PgConnection, group: &PermissionsGroup, user: &User) -> Result<(), Box<dyn Error>> {
+    if group.name == ALL_USERS_GROUP {
+        return Err(crate::error!("the members of `{}` are managed by metabase", ALL_USERS_GROUP));
+    }
+    let member = diesel::select(diesel::dsl::exists(
+        permissions_group_membership::table
+            .filter(permissions_group_membership::group_id.eq(group.id))
+            .filter(permissions_group_membership::user_id.eq(user.id))
+    ))
+    .get_result::<bool>(conn)?;
+    if member {
+        return Err(crate::error!("user is already a member of the group (email: {}, group: {})", user.email, group.name));
+    }
+    diesel::insert_into(permissions_group_membership::table)
+        .values((
+            permissions_group_membership::group_id.eq(group.id),
+            permissions_group_membership::user_id.eq(user.id),
+        ))
+        .execute(conn)?;
+    if group.name == ADMINISTRATORS_GROUP {
+        diesel::update(core_user::table.find(user.id))
+            .set(core_user::is_superuser.eq(true))
+            .execute(conn)?;
+    }
+    Ok(())
+}
+
+fn remove_membership(conn: &PgConnection, group: &PermissionsGroup, user: &User) -> Result<(), Box<dyn Error>> {
+    if group.name == ALL_USERS_GROUP {
+        return Err(crate::error!("the members of `{}` are managed by metabase", ALL_USERS_GROUP));
+    }
+    let removed = diesel::delete(
+        permissions_group_membership::table
+            .filter(permissions_group_membership::group_id.eq(group.id))
+            .filter(permissions_group_membership::user_id.eq(user.id))
+    )
+    .execute(conn)?;
+    if removed == 0 {
+        return Err(crate::error!("user isn't a member of the group (email: {}, group: {})", user.email, group.name));
+    }
+    if group.name == ADMINISTRATORS_GROUP {
+        if other_admins(conn, user)? == 0 {
+            return Err(crate::error!("can't remove the last administrator (email: {})", user.email));
+        }
+        diesel::update(core_user::table.find(user.id))
+            .set(core_user::is_superuser.eq(false))
+            .execute(conn)?;
+    }
+    Ok(())
+}
+
+/// Counts the active superusers other than a user.
+fn other_admins(conn: &PgConnection, user: &User) -> Result<i64, Box<dyn Error>> {
+    let admins = core_user::table
+        .filter(core_user::is_superuser.eq(true))
+        .filter(core_user::is_active.eq(true))
+        .filter(core_user::id.ne(user.id))
+        .count()
+        .get_result::<i64>(conn)?;
+    Ok(admins)
+}
+
+fn load_groups(conn: &PgConnection) -> Result<Vec<GroupRow>, Box<dyn Error>> {
+    let mut groups = permissions_group::table
+        .order(permissions_group::name)
+        .get_results::<PermissionsGroup>(conn)?
+        .into_iter()
+        .map(|group| GroupRow { id: group.id, name: group.name, members: Vec::new() })
+        .collect::<Vec<_>>();
+    let memberships = permissions_group_membership::table
+        .inner_join(core_user::table)
+        .select((permissions_group_membership::group_id, core_user::email))
+        .order(core_user::email)
+        .get_results::<(i32, String)>(conn)?;
+    for (group_id, email) in memberships {
+        if let Some(group) = groups.iter_mut().find(|x| x.id == group_id) {
+            group.members.push(email);
+        }
+    }
+    Ok(groups)
+}
+
+/// Finds the user that created items and revisions are attributed to: the user with an email,
+/// or else the first active administrator.
+pub fn find_creator(conn: &PgConnection, email: Option<&str>) -> Result<i32, Box<dyn Error>> {
+    if let Some(email) = email {
+        let user = find_user(conn, email)?;
+        if !user.is_active {
+            return Err(crate::error!("the creator is deactivated (email: {})", user.email));
+        }
+        return Ok(user.id);
+    }
+    core_user::table
+        .select(core_user::id)
+        .filter(core_user::is_superuser.eq(true))
+        .filter(core_user::is_active.eq(true))
+        .order(core_user::id)
+        .first::<i32>(conn)
+        .optional()?
+        .ok_or_else(|| crate::error!("there's no active administrator to create items as (pass --as-user)").into())
+}
+
+fn find_group(conn: &PgConnection, name: &str) -> Result<PermissionsGroup, Box<dyn Error>> {
+    permissions_group::table
+        .filter(permissions_group::name.eq(name))
+        .get_result::<PermissionsGroup>(conn)
+        .optional()?
+        .ok_or_else(|| crate::error!("group doesn't exist (group: {})", name).into())
+}
+
+fn find_user(conn: &PgConnection, email: &str) -> Result<User, Box<dyn Error>> {
+    core_user::table
+        .select(User::COLUMNS)
+        .get_results::<User>(conn)?
+        .into_iter()
+        .find(|x| x.email.eq_ignore_ascii_case(email))
+        .ok_or_else(|| crate::error!("user doesn't exist (email: {})", email).into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_membership_file_reads_yaml_and_json() {
+        let yaml = "groups:\n  Analysts:\n    - alice@example.com\n    - bob@example.com\n  Empty: []\n";
+        let file = parse_membership_file("groups.yml", yaml).unwrap();
+        assert_eq!(file.groups["Analysts"], vec!["alice@example.com", "bob@example.com"]);
+        assert!(file.groups["Empty"].is_empty());
+        let file = parse_membership_file("groups.json", r#"{"groups":{"Analysts":["alice@example.com"]}}"#).unwrap();
+        assert_eq!(file.groups.len(), 1);
+        assert!(parse_membership_file("groups.json", yaml).is_err());
+    }
+}